@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface AppSettings { suppress_notifications: boolean, include_pre_release: boolean, auto_check_on_launch: boolean, theme: string, cache_enabled: boolean, log_level: string, }
\ No newline at end of file
@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export interface Asset { url: string, browser_download_url: string, id: bigint, node_id: string, name: string, label: string | null, state: string, content_type: string, size: bigint, download_count: bigint, created_at: string, updated_at: string, }
\ No newline at end of file
+export interface Asset { url: string, browser_download_url: string, id: bigint, node_id: string, name: string, label: string | null, state: string, content_type: string, size: bigint, download_count: bigint, created_at: string, updated_at: string, checksum: string | null, expected_sha1: string | null, }
\ No newline at end of file
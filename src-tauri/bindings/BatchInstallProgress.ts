@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { InstallProgress } from "./InstallProgress";
+
+export interface BatchInstallProgress { device_serial: string, progress: InstallProgress, }
\ No newline at end of file
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConnectedDevice } from "./ConnectedDevice";
+import type { ConnectedDeviceType } from "./ConnectedDeviceType";
+import type { Release } from "./Release";
+
+export interface DeepLinkInstall { tag: string, device_type: ConnectedDeviceType, release: Release, device: ConnectedDevice | null, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConnectedDeviceType } from "./ConnectedDeviceType";
+
+export interface DeviceProfile { device_serial: string, device_type: ConnectedDeviceType, include_pre_release: boolean, verify_after_flash: boolean, auto_check_updates: boolean, }
\ No newline at end of file
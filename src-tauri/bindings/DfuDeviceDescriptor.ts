@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface DfuDeviceDescriptor { vid: number, pid: number, product: string, serial: string, interface: number, in_dfu_mode: boolean, bus_powered: boolean, max_power_ma: number, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface DfuDeviceInfo { transfer_size: number, dfu_version: string, can_download: boolean, can_upload: boolean, manifestation_tolerant: boolean, }
\ No newline at end of file
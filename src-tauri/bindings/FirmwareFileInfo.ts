@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FirmwareFormat } from "./FirmwareFormat";
+
+export interface FirmwareFileInfo { file_size: bigint, format: FirmwareFormat, appears_valid: boolean, reason: string | null, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FirmwareTarget } from "./FirmwareTarget";
+
+export interface FirmwareManifest { targets: Array<FirmwareTarget>, }
\ No newline at end of file
@@ -1,4 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
-import type { InstallStatus } from "./InstallStatus";
+import type { InstallStage } from "./InstallStage";
 
-export interface InstallProgress { status: InstallStatus, progress: number, }
\ No newline at end of file
+export interface InstallProgress { stage: InstallStage, bytes_written: bigint, total_bytes: bigint, percent: number, }
\ No newline at end of file
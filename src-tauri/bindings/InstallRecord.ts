@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface InstallRecord { id: bigint, device_serial: string, device_type: string, release_tag: string | null, installed_at: string, success: boolean, error_message: string | null, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface InstallResult { device_serial: string, device_name: string, success: boolean, error: string | null, }
\ No newline at end of file
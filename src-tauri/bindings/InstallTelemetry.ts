@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { InstallStage } from "./InstallStage";
+
+export interface InstallTelemetry { phase_timings: Array<[InstallStage, bigint]>, total_duration_ms: bigint, bytes_per_second: number, }
\ No newline at end of file
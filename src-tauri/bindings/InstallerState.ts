@@ -1,4 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { ConnectedDevice } from "./ConnectedDevice";
 
-export type InstallerState = { type: "Init" } | { type: "Bootloader", device: ConnectedDevice, binary: string, } | { type: "PostInstall" };
\ No newline at end of file
+export type InstallerState = { type: "Init" } | { type: "Bootloader", device: ConnectedDevice, binary: string, verify: boolean, release_tag: string | null, } | { type: "PostInstall" };
\ No newline at end of file
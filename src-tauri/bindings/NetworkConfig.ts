@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface NetworkConfig { proxy_url: string | null, proxy_username: string | null, proxy_password: string | null, no_proxy: Array<string>, }
\ No newline at end of file
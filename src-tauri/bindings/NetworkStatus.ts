@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type InstallStatus = "Preparing" | "Installing";
\ No newline at end of file
+export interface NetworkStatus { online: boolean, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface ReleaseMetadata { tag_name: string, asset_count: number, supported_devices: Array<string>, published_at: string | null, body_html: string, is_prerelease: boolean, }
\ No newline at end of file
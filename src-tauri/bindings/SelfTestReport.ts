@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SelfTestCheck } from "./SelfTestCheck";
+
+export interface SelfTestReport { passed: boolean, checks: Array<SelfTestCheck>, }
\ No newline at end of file
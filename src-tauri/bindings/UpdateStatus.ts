@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type UpdateStatus = { type: "UpToDate" } | { type: "UpdateAvailable", latest_tag: string, current_version: string, } | { type: "UnableToDetermine" };
\ No newline at end of file
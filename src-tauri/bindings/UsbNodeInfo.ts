@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface UsbNodeInfo { vid: number, pid: number, product: string | null, speed: string, bus: number, port_chain: Array<number>, is_hub: boolean, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface VerifyResult { bytes_written: bigint, verified: boolean, mismatched_addresses: Array<number>, }
\ No newline at end of file
@@ -1,3 +1,17 @@
+use std::process::Command;
+
 fn main() {
+    // bake the commit into the binary so support can tell which build someone is running
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .unwrap_or_else(|| String::from("unknown"));
+    println!("cargo:rustc-env=GIT_COMMIT_HASH={commit}");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+
     tauri_build::build();
 }
@@ -0,0 +1,87 @@
+use std::{
+    fs::{create_dir_all, read_to_string, remove_dir_all, write},
+    path::PathBuf,
+};
+
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::{
+    error::Result,
+    github::{Asset, Release},
+    util::app_data_file,
+    FIRMWARE_CACHE_DIR, FIRMWARE_CACHE_INDEX,
+};
+
+/// the last known list of releases for a repo, used when github can't be reached
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct FirmwareCacheIndex {
+    pub releases: Vec<Release>,
+}
+
+/// on-disk cache of downloaded firmware, laid out as `<app data>/firmware_cache/<repo>/<tag>/<asset>`
+pub struct FirmwareCache {
+    root: PathBuf,
+}
+
+impl FirmwareCache {
+    pub fn new(handle: &AppHandle) -> Result<Self> {
+        Ok(FirmwareCache {
+            root: app_data_file(&handle.config(), FIRMWARE_CACHE_DIR)?,
+        })
+    }
+
+    /// where an asset for a given release would live in the cache
+    pub fn path_for(&self, repo: &str, tag: &str, asset: &Asset) -> PathBuf {
+        self.root.join(repo).join(tag).join(&asset.name)
+    }
+
+    /// returns the cached file if it exists and is the size github says it should be
+    pub fn get(&self, repo: &str, tag: &str, asset: &Asset) -> Option<PathBuf> {
+        let path = self.path_for(repo, tag, asset);
+        match path.metadata() {
+            Ok(meta) if meta.len() == asset.size => {
+                debug!("firmware cache hit: {}", path.display());
+                Some(path)
+            }
+            _ => None,
+        }
+    }
+
+    /// make sure the directory for an asset exists, and return the path to write it to
+    pub fn prepare(&self, repo: &str, tag: &str, asset: &Asset) -> Result<PathBuf> {
+        let path = self.path_for(repo, tag, asset);
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        Ok(path)
+    }
+
+    pub fn write_index(&self, repo: &str, index: &FirmwareCacheIndex) -> Result<()> {
+        let dir = self.root.join(repo);
+        create_dir_all(&dir)?;
+        let contents = serde_json::to_string(index)?;
+        Ok(write(dir.join(FIRMWARE_CACHE_INDEX), contents)?)
+    }
+
+    pub fn read_index(&self, repo: &str) -> Option<FirmwareCacheIndex> {
+        let path = self.root.join(repo).join(FIRMWARE_CACHE_INDEX);
+        let contents = read_to_string(&path).ok()?;
+        match serde_json::from_str(&contents) {
+            Ok(index) => Some(index),
+            Err(err) => {
+                error!("corrupt firmware cache index {}: {:?}", path.display(), err);
+                None
+            }
+        }
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        if self.root.exists() {
+            info!("clearing firmware cache: {}", self.root.display());
+            remove_dir_all(&self.root)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use log::info;
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::{
+    commands::logs::parse_log_level,
+    error::Result,
+    logging::LogLevelHandle,
+    state::{AppSettings, SettingsState},
+};
+
+#[derive(TS, Serialize, Clone, Debug)]
+#[ts(export)]
+pub struct AppVersion {
+    pub version: String,
+    pub commit: String,
+}
+
+#[tauri::command]
+/// the updater's own version, and the commit it was built from
+pub fn get_app_version(handle: tauri::AppHandle) -> Result<AppVersion> {
+    // the cargo version is never bumped - tauri.conf.json is the source of truth
+    Ok(AppVersion {
+        version: handle.package_info().version.to_string(),
+        commit: env!("GIT_COMMIT_HASH").to_string(),
+    })
+}
+
+#[tauri::command]
+/// the user's current preferences
+pub fn get_app_settings(state: tauri::State<'_, SettingsState>) -> Result<AppSettings> {
+    Ok(state.settings.read().unwrap().clone())
+}
+
+#[tauri::command]
+/// save the user's preferences
+pub fn set_app_settings(
+    settings: AppSettings,
+    state: tauri::State<'_, SettingsState>,
+    log_level: tauri::State<'_, LogLevelHandle>,
+    handle: tauri::AppHandle,
+) -> Result<()> {
+    // check the log level before saving anything, so a typo can't stick
+    let level = parse_log_level(&settings.log_level)?;
+    settings.save(&handle.config())?;
+    log_level.set(level)?;
+    *state.settings.write().unwrap() = settings;
+    Ok(())
+}
+
+#[tauri::command]
+/// go back to the default preferences
+pub fn reset_app_settings(
+    state: tauri::State<'_, SettingsState>,
+    log_level: tauri::State<'_, LogLevelHandle>,
+    handle: tauri::AppHandle,
+) -> Result<AppSettings> {
+    set_app_settings(AppSettings::default(), state, log_level, handle)?;
+    info!("settings reset to defaults");
+    Ok(AppSettings::default())
+}
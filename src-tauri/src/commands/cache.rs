@@ -0,0 +1,24 @@
+use std::{env::temp_dir, time::Duration};
+
+use crate::{cache::FirmwareCache, commands::github::cleanup_temp_downloads, error::Result};
+
+#[tauri::command]
+/// delete all cached firmware files
+pub fn clear_firmware_cache(handle: tauri::AppHandle) -> Result<()> {
+    FirmwareCache::new(&handle)?.clear()
+}
+
+#[tauri::command]
+/// where downloaded firmware is written when it isn't cached
+pub fn get_temp_dir_path() -> Result<String> {
+    Ok(temp_dir().display().to_string())
+}
+
+#[tauri::command]
+/// delete firmware downloads older than the given age, returning the bytes freed
+pub fn cleanup_old_firmware_files(older_than_hours: u64) -> Result<u64> {
+    // an absurd age just means nothing is old enough - it mustn't wrap around to deleting everything
+    cleanup_temp_downloads(Duration::from_secs(
+        older_than_hours.saturating_mul(60 * 60),
+    ))
+}
@@ -0,0 +1,347 @@
+use std::fs::{read_to_string, write};
+
+use log::{error, info};
+use serde::Serialize;
+use tauri::Manager;
+
+use crate::{
+    device::{midi, ConnectedDevice, ConnectedDeviceType},
+    dfu::{
+        self, detect_device_type, enumerate_dfu_devices, enumerate_usb_tree, find_uf2_disk,
+        read_dfu_device_info, read_usb_strings, uf2_disk_available_space, uf2_disk_name,
+        wait_for_bootloader_mode, wait_for_reconnect, DfuDeviceDescriptor, DfuDeviceInfo,
+        UsbNodeInfo, UsbStrings,
+    },
+    error::{Error, Result},
+    logging::read_last_lines,
+    profiles::DeviceProfile,
+    selftest::{run_selftest, SelfTestReport},
+    state::{DfuConfig, LogDir},
+    DFU_WAIT_TIMEOUT, DIAGNOSTICS_LOG_LINES, USB_BRIDGE_PRODUCT_DFU_ID, USB_BRIDGE_VENDOR_ID,
+    USB_RPI_PRODUCT_BOOTLOADER_ID, USB_RPI_VENDOR_ID,
+};
+
+/// everything support needs to know about a device, written out by `export_device_info`
+#[derive(Serialize, Debug)]
+pub struct DiagnosticsBundle {
+    pub app_version: String,
+    pub device: ConnectedDevice,
+    pub usb: UsbStrings,
+    pub firmware_version: Option<String>,
+    pub rpi_disk_available_space: Option<u64>,
+    pub log_lines: Vec<String>,
+}
+
+#[tauri::command]
+/// enumerate every attached pirate midi device - this does not query device details or releases
+pub fn list_connected_devices() -> Result<Vec<ConnectedDevice>> {
+    let devices: Vec<ConnectedDevice> = usb_enumeration::enumerate(None, None)
+        .iter()
+        .filter(|&device| [USB_BRIDGE_VENDOR_ID, USB_RPI_VENDOR_ID].contains(&device.vendor_id))
+        .map(ConnectedDevice::from)
+        .filter(|device| device.device_type != ConnectedDeviceType::Unknown)
+        .collect();
+
+    info!("found {} connected device(s)", devices.len());
+    Ok(devices)
+}
+
+#[tauri::command]
+/// build a device from its vid/pid, determining the device type from its usb descriptors
+pub fn detect_connected_device(vid: u16, pid: u16) -> Result<ConnectedDevice> {
+    match usb_enumeration::enumerate(Some(vid), Some(pid)).first() {
+        Some(usb_device) => {
+            let mut device = ConnectedDevice::from(usb_device);
+            device.device_type = detect_device_type(vid, pid)?;
+            info!("detected device: {}", device.display_name());
+            Ok(device)
+        }
+        None => err!(Error::USB(format!(
+            "no device found for vid: {vid:#06x}, pid: {pid:#06x}"
+        ))),
+    }
+}
+
+#[tauri::command]
+/// read the firmware version currently installed on a device
+pub async fn read_firmware_version(device: ConnectedDevice) -> Result<String> {
+    // this talks to the device over serial, so keep it off the async runtime
+    tauri::async_runtime::spawn_blocking(move || device_firmware_version(device))
+        .await
+        .map_err(|err| Error::USB(err.to_string()))?
+}
+
+/// read the firmware version currently installed on a device - this blocks while it talks to the device
+pub fn device_firmware_version(mut device: ConnectedDevice) -> Result<String> {
+    match device.device_type {
+        // bridge devices report their version over the device API
+        ConnectedDeviceType::Bridge4 | ConnectedDeviceType::Bridge6 => {
+            if device.device_details.is_none() {
+                device.try_get_device_details()?;
+            }
+            match device.device_details {
+                Some(details) if !details.firmware_version.is_empty() => {
+                    Ok(details.firmware_version)
+                }
+                _ => err!(Error::USB("device did not report a version".to_string())),
+            }
+        }
+        // rpi devices may expose a version file on their mass storage disk
+        ConnectedDeviceType::Click | ConnectedDeviceType::ULoop => {
+            match find_uf2_disk(uf2_disk_name(&device.device_type)) {
+                Some(mount_point) => read_to_string(mount_point.join("version.txt"))
+                    .map(|version| version.trim().to_string())
+                    .map_err(|err| Error::IO(format!("unable to read version file: {}", err))),
+                None => err!(Error::USB(
+                    "version not readable in current mode".to_string()
+                )),
+            }
+        }
+        _ => err!(Error::USB(
+            "version not readable in current mode".to_string()
+        )),
+    }
+}
+
+#[tauri::command]
+/// write a json diagnostics bundle for a device to disk
+pub async fn export_device_info(
+    device: ConnectedDevice,
+    output_path: String,
+    log_dir: tauri::State<'_, LogDir>,
+    handle: tauri::AppHandle,
+) -> Result<()> {
+    let app_version = handle.package_info().version.to_string();
+    let log_file = log_dir.current_file();
+
+    // this talks to the device and reads from disk, so keep it off the async runtime
+    tauri::async_runtime::spawn_blocking(move || -> Result<()> {
+        let usb = match read_usb_strings(device.vendor_id, device.product_id) {
+            Ok(strings) => strings,
+            Err(err) => {
+                error!("unable to read usb strings: {:?}", err);
+                UsbStrings::default()
+            }
+        };
+
+        // grab the tail of the current log
+        let log_lines = match read_last_lines(&log_file, DIAGNOSTICS_LOG_LINES) {
+            Ok(lines) => lines,
+            Err(err) => {
+                error!("unable to read log file: {:?}", err);
+                vec![]
+            }
+        };
+
+        let bundle = DiagnosticsBundle {
+            app_version,
+            firmware_version: device_firmware_version(device.clone()).ok(),
+            rpi_disk_available_space: uf2_disk_available_space(uf2_disk_name(&device.device_type)),
+            device,
+            usb,
+            log_lines,
+        };
+
+        let contents = serde_json::to_string_pretty(&bundle)?;
+        write(&output_path, contents)?;
+        info!("diagnostics written to: {output_path}");
+        Ok(())
+    })
+    .await
+    .map_err(|err| Error::IO(err.to_string()))?
+}
+
+#[tauri::command]
+/// every connected device with a DFU interface, including ones we don't recognise
+pub async fn list_dfu_devices(handle: tauri::AppHandle) -> Result<Vec<DfuDeviceDescriptor>> {
+    let devices = tauri::async_runtime::spawn_blocking(enumerate_dfu_devices)
+        .await
+        .map_err(|err| Error::USB(err.to_string()))??;
+
+    // let the ui suggest a powered hub before a flash fails halfway through
+    for device in devices.iter().filter(|device| device.is_underpowered()) {
+        handle.emit_all("low_bus_power", device.clone()).unwrap();
+    }
+    Ok(devices)
+}
+
+#[tauri::command]
+/// the saved preferences for a device
+pub fn get_device_profile(serial: String, handle: tauri::AppHandle) -> Result<DeviceProfile> {
+    DeviceProfile::load(&handle, &serial)
+}
+
+#[tauri::command]
+/// save the preferences for a device
+pub fn save_device_profile(profile: DeviceProfile, handle: tauri::AppHandle) -> Result<()> {
+    profile.save(&handle)?;
+    info!("saved profile for {}", profile.device_serial);
+    Ok(())
+}
+
+#[tauri::command]
+/// every midi output port the os knows about - for checking a device shows up as a midi device at all
+pub fn list_midi_ports() -> Result<Vec<String>> {
+    midi::list_midi_ports()
+}
+
+#[tauri::command]
+/// the midi output port a device is connected on
+pub fn get_device_midi_port(device: ConnectedDevice) -> Result<String> {
+    midi::get_midi_port_name(&device)
+}
+
+#[tauri::command]
+/// every usb device and the hub it's plugged into - for support to spot hub and bandwidth problems
+pub async fn get_usb_device_tree() -> Result<Vec<UsbNodeInfo>> {
+    tauri::async_runtime::spawn_blocking(enumerate_usb_tree)
+        .await
+        .map_err(|err| Error::USB(err.to_string()))?
+}
+
+#[tauri::command]
+/// check a device in its bootloader is ready to flash - sends `selftest_progress` as each check finishes
+pub async fn run_device_selftest(
+    device: ConnectedDevice,
+    handle: tauri::AppHandle,
+) -> Result<SelfTestReport> {
+    let address = *handle.state::<DfuConfig>().address.read().unwrap();
+
+    // reading flash and the bootloader disk both block, so keep them off the async runtime
+    tauri::async_runtime::spawn_blocking(move || {
+        run_selftest(&device.device_type, address, &handle)
+    })
+    .await
+    .map_err(|err| Error::USB(err.to_string()))?
+}
+
+#[tauri::command]
+/// whether a bridge in its bootloader has never had firmware installed - reads its flash, so don't call it mid-install
+pub async fn is_factory_fresh(device: ConnectedDevice) -> Result<bool> {
+    tauri::async_runtime::spawn_blocking(move || device.is_factory_fresh())
+        .await
+        .map_err(|err| Error::USB(err.to_string()))
+}
+
+#[tauri::command]
+/// read the DFU attributes (transfer size, version, capabilities) of a device
+pub async fn get_dfu_device_info(vid: u16, pid: u16) -> Result<DfuDeviceInfo> {
+    tauri::async_runtime::spawn_blocking(move || read_dfu_device_info(vid, pid))
+        .await
+        .map_err(|err| Error::USB(err.to_string()))?
+}
+
+/// the vid/pid a device enumerates with once it's in its bootloader
+fn bootloader_ids(device_type: &ConnectedDeviceType) -> Result<(u16, u16)> {
+    match device_type {
+        ConnectedDeviceType::Bridge6
+        | ConnectedDeviceType::Bridge4
+        | ConnectedDeviceType::BridgeBootloader => {
+            Ok((USB_BRIDGE_VENDOR_ID, USB_BRIDGE_PRODUCT_DFU_ID))
+        }
+        ConnectedDeviceType::Click
+        | ConnectedDeviceType::ULoop
+        | ConnectedDeviceType::RPBootloader => {
+            Ok((USB_RPI_VENDOR_ID, USB_RPI_PRODUCT_BOOTLOADER_ID))
+        }
+        ConnectedDeviceType::Unknown => err!(Error::Bootloader(
+            "unable to determine the bootloader for an unknown device".to_string()
+        )),
+    }
+}
+
+#[tauri::command]
+/// wait for a device to come back in its bootloader - e.g. after the user holds the button and re-plugs it
+pub async fn wait_for_device_bootloader(
+    device: ConnectedDevice,
+    timeout_secs: u64,
+    handle: tauri::AppHandle,
+) -> Result<()> {
+    let (vid, pid) = bootloader_ids(&device.device_type)?;
+
+    // polling blocks, so keep it off the async runtime
+    tauri::async_runtime::spawn_blocking(move || wait_for_bootloader_mode(vid, pid, timeout_secs))
+        .await
+        .map_err(|err| Error::Bootloader(err.to_string()))??;
+
+    handle.emit_all("bootloader_detected", device).unwrap();
+    Ok(())
+}
+
+#[tauri::command]
+/// ask a device to reboot into its bootloader, then wait for it to show up - no button holding required
+pub async fn enter_bootloader_mode(
+    device: ConnectedDevice,
+    timeout_secs: Option<u64>,
+    handle: tauri::AppHandle,
+) -> Result<()> {
+    let (vid, pid) = bootloader_ids(&device.device_type)?;
+    let timeout_secs = timeout_secs.unwrap_or(DFU_WAIT_TIMEOUT);
+
+    // both the request and the polling block, so keep them off the async runtime
+    let target = device.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        target.enter_bootloader()?;
+        wait_for_bootloader_mode(vid, pid, timeout_secs)
+    })
+    .await
+    .map_err(|err| Error::Bootloader(err.to_string()))??;
+
+    handle.emit_all("bootloader_detected", device).unwrap();
+    Ok(())
+}
+
+#[tauri::command]
+/// free space (in bytes) on the disk a uf2 device mounts in its bootloader
+pub async fn get_available_disk_space(device: ConnectedDevice) -> Result<u64> {
+    match device.device_type {
+        ConnectedDeviceType::Click
+        | ConnectedDeviceType::ULoop
+        | ConnectedDeviceType::RPBootloader => (),
+        _ => err!(Error::Incompatable(
+            "only uf2 devices mount a disk".to_string()
+        )),
+    }
+
+    // scanning disks blocks, so keep it off the async runtime
+    let available = tauri::async_runtime::spawn_blocking(move || {
+        uf2_disk_available_space(uf2_disk_name(&device.device_type))
+    })
+    .await
+    .map_err(|err| Error::IO(err.to_string()))?;
+    match available {
+        Some(available) => Ok(available),
+        None => err!(Error::Install("disk not found".to_string())),
+    }
+}
+
+#[tauri::command]
+/// wait for a device to come back after an install, returning it as it's now connected
+pub async fn wait_for_device_reconnect(
+    original_serial: String,
+    normal_pid: u16,
+    timeout_secs: u64,
+    handle: tauri::AppHandle,
+) -> Result<ConnectedDevice> {
+    // polling blocks, so keep it off the async runtime
+    let usb_device = tauri::async_runtime::spawn_blocking(move || {
+        wait_for_reconnect(&original_serial, normal_pid, timeout_secs)
+    })
+    .await
+    .map_err(|err| Error::USB(err.to_string()))??;
+
+    let device = ConnectedDevice::from(&usb_device);
+    handle.emit_all("device_reconnected", &device).unwrap();
+    Ok(device)
+}
+
+#[tauri::command]
+/// read back a region of flash from a device in DFU mode
+pub async fn read_firmware_bytes(vid: u16, pid: u16, address: u32, length: u32) -> Result<Vec<u8>> {
+    // uploads block, so keep them off the async runtime
+    tauri::async_runtime::spawn_blocking(move || {
+        dfu::read_firmware_bytes(vid, pid, address, length)
+    })
+    .await
+    .map_err(|err| Error::USB(err.to_string()))?
+}
@@ -1,17 +1,36 @@
-use log::{error, info, trace};
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, USER_AGENT};
-use reqwest::StatusCode;
+use futures::future::join_all;
+use futures::StreamExt;
+use log::{debug, error, info, warn};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, IF_NONE_MATCH, USER_AGENT};
+use reqwest::{NoProxy, Proxy, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::env::{self, temp_dir};
-use std::fs::File;
-use std::io::{copy, Cursor};
+use std::fs::{read_dir, remove_file, File};
+use std::io::Write;
 use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+use ts_rs::TS;
 
+use crate::cache::{FirmwareCache, FirmwareCacheIndex};
+use crate::commands::device::device_firmware_version;
 use crate::device::{ConnectedDevice, ConnectedDeviceType};
 use crate::error::{Error, Result};
-use crate::github::Release;
-use crate::{GITHUB_API_URL, GITHUB_BRIDGE_REPO, GITHUB_CLICK_REPO, GITHUB_ORG, GITHUB_ULOOP_REPO};
+use crate::github::{
+    filter_releases_newer_than, Asset, ByteStream, FirmwareManifest, GithubClient,
+    RateLimitResponse, RateLimitStatus, Release, ReleasesResponse, Repository,
+};
+use crate::notifications::notify_update_available;
+use crate::state::{
+    GithubConfig, GithubState, NetworkConfig, NetworkState, ReleaseEtagCache, SettingsState,
+};
+use crate::validation::{file_sha1, parse_version, verify_checksum};
+use crate::version::{filter_by_semver_range, parse_range};
+use crate::{
+    GITHUB_API_URL, GITHUB_FIRMWARE_REPO_PREFIX, GITHUB_ORG, GITHUB_PING_TIMEOUT,
+    GITHUB_RATE_LIMIT_TTL, GITHUB_RELEASES_PER_PAGE,
+};
 
 #[derive(Serialize, Deserialize)]
 struct Query {
@@ -19,144 +38,1539 @@ struct Query {
     page: u32,
 }
 
-fn build_headers() -> HeaderMap {
+#[derive(TS, Serialize, Clone, Debug)]
+#[ts(export)]
+pub struct DownloadProgress {
+    bytes_received: u64,
+    total_bytes: Option<u64>,
+}
+
+/// sent with `hash_mismatch` when a download doesn't match the sha1 listed in its release notes
+#[derive(TS, Serialize, Clone, Debug)]
+#[ts(export)]
+pub struct HashMismatch {
+    asset_name: String,
+    expected: String,
+    got: String,
+}
+
+/// every release for every device, as written by `export_release_catalog`
+#[derive(Serialize, Debug)]
+pub struct ReleaseCatalog {
+    /// unix timestamp, in seconds
+    fetched_at: u64,
+    /// keyed by repo - bridge 4s and 6s share one
+    releases: HashMap<String, Vec<Release>>,
+}
+
+/// everything the release detail page shows about a release
+#[derive(TS, Serialize, Clone, Debug)]
+#[ts(export)]
+pub struct ReleaseMetadata {
+    tag_name: String,
+    asset_count: usize,
+    supported_devices: Vec<String>,
+    published_at: Option<String>,
+    body_html: String,
+    is_prerelease: bool,
+}
+
+fn build_headers(config: &GithubConfig) -> HeaderMap {
     // create some headers for our fetching
     let mut headers = HeaderMap::new();
 
     // add the user-agent header required by github
     headers.insert(USER_AGENT, HeaderValue::from_static("reqwest"));
 
-    // add the authorization header if the enviroment variable GITHUB_TOKEN is defined
+    // add the authorization header if the user has saved a token, or the enviroment variable GITHUB_TOKEN is defined
     // this is good for developing, as the rate limit for unauthencated requests is 65 requests/hour
-    match env::var("GITHUB_TOKEN") {
-        Ok(token) => match HeaderValue::from_str(format!("Bearer {}", token).as_str()) {
+    match config
+        .token
+        .clone()
+        .or_else(|| env::var("GITHUB_TOKEN").ok())
+    {
+        Some(token) => match HeaderValue::from_str(format!("Bearer {}", token).as_str()) {
             Ok(val) => _ = headers.insert(AUTHORIZATION, val),
-            Err(err) => error!("GITHUB_TOKEN not set correctly - {err}: {token}"),
+            Err(err) => error!("github token not set correctly - {err}"),
         },
-        Err(_) => {
-            info!("environment variable GITHUB_TOKEN is not set")
+        None => {
+            info!("no github token is configured")
         }
     };
 
     headers
 }
 
-async fn get_releases(device: &ConnectedDevice, repo: &str) -> Result<Vec<Release>> {
+/// the github settings currently held in app state
+pub fn github_config(handle: &AppHandle) -> GithubConfig {
+    handle.state::<GithubState>().config.read().unwrap().clone()
+}
+
+/// a client builder with the user's proxy settings applied
+fn client_builder(config: &NetworkConfig) -> Result<reqwest::ClientBuilder> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(url) = config.proxy_url.as_deref().filter(|url| !url.is_empty()) {
+        let mut proxy =
+            Proxy::all(url).map_err(|err| Error::Http(format!("invalid proxy url: {err}")))?;
+        if let Some(username) = &config.proxy_username {
+            proxy = proxy.basic_auth(username, config.proxy_password.as_deref().unwrap_or(""));
+        }
+        if !config.no_proxy.is_empty() {
+            proxy = proxy.no_proxy(NoProxy::from_string(&config.no_proxy.join(",")));
+        }
+        builder = builder.proxy(proxy);
+    }
+    Ok(builder)
+}
+
+/// an http client that goes through the user's proxy, if they've set one
+pub fn build_http_client(config: &NetworkConfig) -> Result<reqwest::Client> {
+    Ok(client_builder(config)?.build()?)
+}
+
+/// an http client using the network settings currently held in app state
+pub fn http_client(handle: &AppHandle) -> Result<reqwest::Client> {
+    build_http_client(&handle.state::<NetworkState>().config.read().unwrap())
+}
+
+/// how rate limited github requests are retried
+pub struct RetryPolicy {
+    pub max_retries: u8,
+    pub max_wait_secs: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            max_wait_secs: 60,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// wait until github says our quota resets, falling back to an exponential delay - always capped at `max_wait_secs`
+    fn wait_duration(&self, attempt: u8, reset_at: Option<u64>) -> Duration {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let wait_secs = reset_at
+            .map(|reset| reset.saturating_sub(now))
+            .unwrap_or(1 << attempt);
+
+        Duration::from_secs(wait_secs.min(self.max_wait_secs))
+    }
+}
+
+/// retrieve every release in a repo, a page at a time, until github runs out
+async fn get_all_releases_paginated(
+    client: &impl GithubClient,
+    repo: &str,
+    policy: &RetryPolicy,
+    config: &GithubConfig,
+    etags: Option<&ReleaseEtagCache>,
+) -> Result<Vec<Release>> {
+    let mut releases = Vec::new();
+    let mut page: u32 = 1;
+    loop {
+        let batch = fetch_releases_page(client, repo, page, policy, config, etags).await?;
+        let count = batch.len();
+        releases.extend(batch);
+
+        // a short page is the last one
+        if count < GITHUB_RELEASES_PER_PAGE as usize {
+            debug!("fetched {} releases over {page} page(s)", releases.len());
+            return Ok(releases);
+        }
+        page += 1;
+    }
+}
+
+/// retrieve a single page of releases, retrying when rate limited.
+/// with an etag cache, pages github says haven't changed are served from the cache.
+async fn fetch_releases_page(
+    client: &impl GithubClient,
+    repo: &str,
+    page: u32,
+    policy: &RetryPolicy,
+    config: &GithubConfig,
+    etags: Option<&ReleaseEtagCache>,
+) -> Result<Vec<Release>> {
     // retrieve the releases!
-    let url = format!("{}/repos/{}/{}/releases", GITHUB_API_URL, GITHUB_ORG, repo);
-    let request = reqwest::Client::new()
-        .get(url)
-        .headers(build_headers())
-        .send();
-    match request.await {
-        Ok(res) => {
-            trace!("success [raw]: {:?}", res);
-            match res.status() {
-                StatusCode::OK => match res.json::<Vec<Release>>().await {
-                    Ok(releases) => {
-                        let compatible: Vec<Release> = releases
-                            .iter()
-                            .filter(|&release| {
-                                // find releases compatible with our device
-                                release
-                                    .assets
-                                    .iter()
-                                    .any(|asset| asset.is_compatible(device))
-                            })
-                            .cloned()
-                            .collect::<Vec<Release>>();
-                        // trace!("compatible releases: {:?}", compatible);
-                        Ok(compatible)
-                    }
-                    Err(err) => err!(Error::Http(err.to_string())),
-                },
-                StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS => {
-                    log::error!("Rate limited from Github - headers: {:?}", res.headers());
-                    err!(Error::Http("Github rate limit hit!".to_string()))
+    let url = format!(
+        "{}/repos/{}/{}/releases?per_page={}&page={}",
+        config.base_url, GITHUB_ORG, repo, GITHUB_RELEASES_PER_PAGE, page
+    );
+    let mut headers = build_headers(config);
+    if let Some(etag) = etags.and_then(|etags| etags.etag(&url)) {
+        match HeaderValue::from_str(&etag) {
+            Ok(value) => _ = headers.insert(IF_NONE_MATCH, value),
+            Err(err) => error!("cached etag is not a valid header - {err}"),
+        }
+    }
+
+    let mut attempt: u8 = 0;
+    loop {
+        match client.get_releases(&url, headers.clone()).await {
+            Ok(ReleasesResponse::Releases { releases, etag }) => {
+                if let (Some(etags), Some(etag)) = (etags, etag) {
+                    etags.insert(&url, etag, releases.clone());
                 }
-                status => {
-                    err!(Error::Http(
-                        format!("recieved an unsupported http status code: {status}").to_string()
-                    ))
+                return Ok(releases);
+            }
+            Ok(ReleasesResponse::NotModified) => {
+                debug!("releases unchanged since the last request: {url}");
+                return match etags.and_then(|etags| etags.releases(&url)) {
+                    Some(releases) => Ok(releases),
+                    None => Err(Error::Http(
+                        "github returned 304 for a request we have no cached copy of".to_string(),
+                    )),
+                };
+            }
+            Err(Error::RateLimited { reset_at }) if attempt < policy.max_retries => {
+                // back off before trying again
+                let wait = policy.wait_duration(attempt, reset_at);
+                attempt += 1;
+                info!(
+                    "retrying in {}s (attempt {attempt} of {})",
+                    wait.as_secs(),
+                    policy.max_retries
+                );
+                tokio::time::sleep(wait).await;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// every release in a repo - from github when we can reach it, otherwise from the firmware cache index
+struct RepoReleases {
+    releases: Vec<Release>,
+    /// read from the cache index, so only releases with a cached asset can be installed
+    offline: bool,
+}
+
+async fn get_repo_releases(
+    client: &impl GithubClient,
+    repo: &str,
+    policy: &RetryPolicy,
+    config: &GithubConfig,
+    etags: Option<&ReleaseEtagCache>,
+    cache: Option<&FirmwareCache>,
+    online: bool,
+) -> Result<RepoReleases> {
+    // don't bother waiting on a request we already know will time out
+    let fetched = match online {
+        true => get_all_releases_paginated(client, repo, policy, config, etags).await,
+        false => Err(Error::Offline(
+            "github did not respond to a ping".to_string(),
+        )),
+    };
+    match fetched {
+        Ok(mut releases) => {
+            let known = cache
+                .and_then(|cache| cache.read_index(repo))
+                .map(|index| index.releases)
+                .unwrap_or_default();
+            load_manifests(client, &mut releases, config, &known).await;
+
+            // keep the index up to date so we have something to show when offline
+            if let Some(cache) = cache {
+                let index = FirmwareCacheIndex {
+                    releases: releases.clone(),
+                };
+                if let Err(err) = cache.write_index(repo, &index) {
+                    error!("unable to write firmware cache index: {:?}", err);
                 }
             }
+            Ok(RepoReleases {
+                releases,
+                offline: false,
+            })
         }
+        Err(Error::Offline(reason)) => match cache.and_then(|cache| cache.read_index(repo)) {
+            Some(index) => {
+                info!("github is unreachable - falling back to the firmware cache");
+                Ok(RepoReleases {
+                    releases: index.releases,
+                    offline: true,
+                })
+            }
+            None => err!(Error::Offline(reason)),
+        },
+        Err(err) => Err(err),
+    }
+}
+
+/// the releases in a repo that can be installed on a device
+fn compatible_releases(
+    repo_releases: &RepoReleases,
+    device: &ConnectedDevice,
+    repo: &str,
+    include_pre_release: bool,
+    include_drafts: bool,
+    cache: Option<&FirmwareCache>,
+) -> Vec<Release> {
+    repo_releases
+        .releases
+        .iter()
+        .filter(|&release| {
+            // a developer token can see drafts, but they aren't meant for the public
+            include_drafts || !release.is_draft()
+        })
+        .filter(|&release| {
+            // skip pre-releases unless we've opted into them
+            include_pre_release || !release.prerelease
+        })
+        .filter(|&release| {
+            // find releases compatible with our device - when offline, only ones we can actually install without a connection
+            match release.asset_for_device(device) {
+                Some(asset) if repo_releases.offline => cache.map_or(false, |cache| {
+                    cache.get(repo, &release.tag_name, asset).is_some()
+                }),
+                Some(_) => true,
+                None => false,
+            }
+        })
+        .cloned()
+        .collect::<Vec<Release>>()
+}
+
+async fn get_releases(
+    client: &impl GithubClient,
+    device: &ConnectedDevice,
+    repo: &str,
+    include_pre_release: bool,
+    include_drafts: bool,
+    policy: &RetryPolicy,
+    config: &GithubConfig,
+    etags: Option<&ReleaseEtagCache>,
+    cache: Option<&FirmwareCache>,
+    online: bool,
+) -> Result<Vec<Release>> {
+    let repo_releases =
+        get_repo_releases(client, repo, policy, config, etags, cache, online).await?;
+    Ok(compatible_releases(
+        &repo_releases,
+        device,
+        repo,
+        include_pre_release,
+        include_drafts,
+        cache,
+    ))
+}
+
+/// download and parse the manifest of every release that has one - a bad manifest falls back to file names.
+/// manifests in `known` (e.g. the cache index) are reused, and the rest are all downloaded at once.
+async fn load_manifests(
+    client: &impl GithubClient,
+    releases: &mut [Release],
+    config: &GithubConfig,
+    known: &[Release],
+) {
+    let downloads = releases.iter_mut().filter_map(|release| {
+        let (asset_id, url) = release
+            .manifest_asset()
+            .map(|asset| (asset.id, asset.browser_download_url.clone()))?;
+
+        // a manifest only changes when its asset is uploaded again, which gives it a new id
+        let cached = known
+            .iter()
+            .find(|known| {
+                known.id == release.id
+                    && known.manifest_asset().map(|asset| asset.id) == Some(asset_id)
+            })
+            .and_then(|known| known.manifest.clone());
+        if cached.is_some() {
+            release.manifest = cached;
+            return None;
+        }
+
+        Some(async move {
+            let manifest = match client.fetch_bytes(&url, build_headers(config)).await {
+                Ok(download) => download.into_bytes().await.and_then(|bytes| {
+                    serde_json::from_slice::<FirmwareManifest>(&bytes).map_err(Error::from)
+                }),
+                Err(err) => Err(err),
+            };
+            match manifest {
+                Ok(manifest) => release.manifest = Some(manifest),
+                Err(err) => error!(
+                    "unable to load manifest for {}: {:?}",
+                    release.tag_name, err
+                ),
+            }
+        })
+    });
+    join_all(downloads).await;
+}
+
+/// open the firmware cache, logging rather than failing when it's unavailable
+fn open_cache(handle: &AppHandle) -> Option<FirmwareCache> {
+    if !handle
+        .state::<SettingsState>()
+        .settings
+        .read()
+        .unwrap()
+        .cache_enabled
+    {
+        return None;
+    }
+    match FirmwareCache::new(handle) {
+        Ok(cache) => Some(cache),
         Err(err) => {
-            trace!("error [raw]: {:?}", err);
-            Err(Error::Http(err.to_string()))
+            error!("firmware cache unavailable: {:?}", err);
+            None
         }
     }
 }
 
+#[derive(TS, Serialize, Clone, Debug)]
+#[ts(export)]
+pub struct NetworkStatus {
+    online: bool,
+}
+
+/// quickly check whether github is reachable, letting the frontend know either way
+pub async fn ping_github(handle: &AppHandle) -> bool {
+    let network = handle
+        .state::<NetworkState>()
+        .config
+        .read()
+        .unwrap()
+        .clone();
+    let config = github_config(handle);
+    let request = client_builder(&network)
+        .and_then(|builder| Ok(builder.timeout(GITHUB_PING_TIMEOUT).build()?))
+        .map(|client| {
+            client
+                .head(&config.base_url)
+                .headers(build_headers(&config))
+                .send()
+        });
+
+    let online = match request {
+        Ok(request) => match request.await {
+            Ok(res) => res.status().is_success() || res.status().is_redirection(),
+            Err(err) => {
+                info!("github is unreachable: {:?}", err);
+                false
+            }
+        },
+        Err(err) => {
+            error!("unable to build http client: {:?}", err);
+            false
+        }
+    };
+
+    handle
+        .emit_all("network_status", NetworkStatus { online })
+        .unwrap();
+    online
+}
+
+#[tauri::command]
+/// check whether github is reachable before fetching anything from it
+pub async fn ping_github_api(handle: AppHandle) -> Result<bool> {
+    Ok(ping_github(&handle).await)
+}
+
 #[tauri::command]
 /// retrieve all compatable github releases
-pub async fn fetch_releases(device: ConnectedDevice) -> Result<Vec<Release>> {
+pub async fn fetch_releases(
+    device: ConnectedDevice,
+    include_pre_release: Option<bool>,
+    include_drafts: Option<bool>,
+    handle: AppHandle,
+) -> Result<Vec<Release>> {
     // perform the fetch
     info!("fetching releases from github...");
+    let device = match device.device_type {
+        ConnectedDeviceType::BridgeBootloader => identify_bootloader_bridge(device).await?,
+        _ => device,
+    };
 
     // determine which repo to get
-    match &device.device_type {
-        ConnectedDeviceType::BridgeBootloader
-        | ConnectedDeviceType::RPBootloader
-        | ConnectedDeviceType::Unknown => Err(Error::Other(
-            "github releases do not exist for this device type".to_string(),
-        )),
-        ConnectedDeviceType::Bridge4 | ConnectedDeviceType::Bridge6 => {
-            get_releases(&device, GITHUB_BRIDGE_REPO).await
+    let config = github_config(&handle);
+    let repo = config.repo_for(&device.device_type)?;
+    get_releases(
+        &http_client(&handle)?,
+        &device,
+        &repo,
+        include_pre_release.unwrap_or_else(|| {
+            handle
+                .state::<SettingsState>()
+                .settings
+                .read()
+                .unwrap()
+                .include_pre_release
+        }),
+        include_drafts.unwrap_or(false),
+        &RetryPolicy::default(),
+        &config,
+        Some(&handle.state::<GithubState>().release_etags),
+        open_cache(&handle).as_ref(),
+        ping_github(&handle).await,
+    )
+    .await
+}
+
+/// a bridge that shows up in its bootloader - e.g. a new one that's never been flashed - gets releases for
+/// its model when the bootloader says which it is, otherwise every bridge release to choose from
+async fn identify_bootloader_bridge(device: ConnectedDevice) -> Result<ConnectedDevice> {
+    tauri::async_runtime::spawn_blocking(move || match device.bootloader_bridge_model() {
+        Some(model) => {
+            info!("bridge bootloader identified as a {}", model.display_name());
+            ConnectedDevice {
+                device_type: model,
+                ..device
+            }
+        }
+        None => device,
+    })
+    .await
+    .map_err(|err| Error::USB(err.to_string()))
+}
+
+#[tauri::command]
+/// retrieve compatable releases for several devices at once, keyed by serial number.
+/// repos are fetched concurrently, and only once each - bridge 4s and 6s share a repo.
+pub async fn batch_fetch_releases(
+    devices: Vec<ConnectedDevice>,
+    handle: AppHandle,
+) -> Result<HashMap<String, Vec<Release>>> {
+    info!(
+        "fetching releases for {} devices from github...",
+        devices.len()
+    );
+
+    // bridges in their bootloader get the same releases `fetch_releases` would give them
+    let mut resolved = Vec::with_capacity(devices.len());
+    for device in devices {
+        resolved.push(match device.device_type {
+            ConnectedDeviceType::BridgeBootloader => identify_bootloader_bridge(device).await?,
+            _ => device,
+        });
+    }
+    let devices = resolved;
+
+    let config = github_config(&handle);
+    let client = http_client(&handle)?;
+    let policy = RetryPolicy::default();
+    let cache = open_cache(&handle);
+    let etags = &handle.state::<GithubState>().release_etags;
+    let online = ping_github(&handle).await;
+    let include_pre_release = handle
+        .state::<SettingsState>()
+        .settings
+        .read()
+        .unwrap()
+        .include_pre_release;
+
+    let mut repos: Vec<String> = devices
+        .iter()
+        .filter_map(|device| config.repo_for(&device.device_type).ok())
+        .collect();
+    repos.sort();
+    repos.dedup();
+
+    let fetched = join_all(repos.iter().map(|repo| {
+        get_repo_releases(
+            &client,
+            repo,
+            &policy,
+            &config,
+            Some(etags),
+            cache.as_ref(),
+            online,
+        )
+    }))
+    .await;
+    let mut by_repo = HashMap::new();
+    for (repo, result) in repos.iter().zip(fetched) {
+        // one repo failing shouldn't cost every other device its releases
+        match result {
+            Ok(repo_releases) => {
+                by_repo.insert(repo.as_str(), repo_releases);
+            }
+            Err(err) => error!("unable to fetch releases for {repo}: {:?}", err),
         }
-        ConnectedDeviceType::Click => get_releases(&device, GITHUB_CLICK_REPO).await,
-        ConnectedDeviceType::ULoop => get_releases(&device, GITHUB_ULOOP_REPO).await,
     }
+
+    let mut releases = HashMap::new();
+    for device in &devices {
+        // devices without a serial can't be told apart, and unsupported ones have nothing to offer
+        let (serial, repo) = match (&device.serial_number, config.repo_for(&device.device_type)) {
+            (Some(serial), Ok(repo)) => (serial, repo),
+            _ => continue,
+        };
+        let repo_releases = match by_repo.get(repo.as_str()) {
+            Some(repo_releases) => repo_releases,
+            None => continue,
+        };
+        let compatible = compatible_releases(
+            repo_releases,
+            device,
+            &repo,
+            include_pre_release,
+            false,
+            cache.as_ref(),
+        );
+        releases.insert(serial.clone(), compatible);
+    }
+    Ok(releases)
 }
 
-/// retrieve specific binary asset and save to the filesystem
-pub async fn fetch_compatable_asset(device: &ConnectedDevice, release: Release) -> Result<PathBuf> {
-    match release.assets.iter().find(|&a| a.is_compatible(device)) {
-        Some(asset) => {
-            // download the binary
-            info!("fetching asset from github: {}", asset.browser_download_url);
-            let request = reqwest::Client::new()
-                .get(asset.browser_download_url.clone())
-                .headers(build_headers())
-                .send();
-
-            // TODO: clean up this fuckin mess
-            match request.await {
-                Ok(response) => match response.bytes().await {
-                    Ok(payload) => {
-                        // create timestamp
-                        let time = SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap()
-                            .as_millis();
-                        // create temp file
-                        let temp_file_path = temp_dir().join(format!("{time}-{}", asset.name));
-                        info!("downloading file to: {}", temp_file_path.display());
-                        // create temp file
-                        match File::create(&temp_file_path) {
-                            Ok(mut file) => {
-                                let mut content = Cursor::new(payload);
-                                match copy(&mut content, &mut file) {
-                                    Ok(written) => {
-                                        info!(
-                                            "successfully downloaded - total bytes written: {}",
-                                            written
-                                        );
-                                        Ok(temp_file_path)
-                                    }
-                                    Err(err) => err!(Error::IO(err.to_string())),
-                                }
-                            }
-                            Err(err) => err!(Error::IO(err.to_string())),
-                        }
-                    }
-                    Err(err) => err!(Error::Http(err.to_string())),
-                },
-                Err(err) => err!(Error::Http(err.to_string())),
+#[tauri::command]
+/// write every release for every supported device to a json file, so it can be mirrored for offline installs
+pub async fn export_release_catalog(output_path: String, handle: AppHandle) -> Result<()> {
+    let config = github_config(&handle);
+    let client = http_client(&handle)?;
+    let policy = RetryPolicy::default();
+    let etags = &handle.state::<GithubState>().release_etags;
+
+    let mut repos: Vec<String> = config.repos.values().cloned().collect();
+    repos.sort();
+    repos.dedup();
+    info!("exporting the release catalog for {} repos...", repos.len());
+
+    // always export what's on github right now - never a stale copy from the firmware cache
+    let online = ping_github(&handle).await;
+    let fetched =
+        join_all(repos.iter().map(|repo| {
+            get_repo_releases(&client, repo, &policy, &config, Some(etags), None, online)
+        }))
+        .await;
+
+    let mut releases = HashMap::new();
+    for (repo, result) in repos.into_iter().zip(fetched) {
+        let mut repo_releases = result?.releases;
+        let mut seen = HashSet::new();
+        repo_releases.retain(|release| seen.insert(release.tag_name.clone()));
+        releases.insert(repo, repo_releases);
+    }
+    let catalog = ReleaseCatalog {
+        fetched_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+        releases,
+    };
+
+    let contents = serde_json::to_string_pretty(&catalog)?;
+    std::fs::write(&output_path, contents)?;
+    info!("release catalog written to: {output_path}");
+    Ok(())
+}
+
+#[tauri::command]
+/// retrieve the compatable github releases published in the last `days` days
+pub async fn fetch_recent_releases(
+    device: ConnectedDevice,
+    days: u32,
+    handle: AppHandle,
+) -> Result<Vec<Release>> {
+    let releases = fetch_releases(device, None, None, handle).await?;
+    Ok(filter_releases_newer_than(&releases, days))
+}
+
+#[tauri::command]
+/// retrieve the compatable github releases within a version constraint, e.g. `^1.0` to stay on major version 1
+pub async fn fetch_releases_in_range(
+    device: ConnectedDevice,
+    range: String,
+    handle: AppHandle,
+) -> Result<Vec<Release>> {
+    // check the range before going to github
+    parse_range(&range)?;
+    let releases = fetch_releases(device, None, None, handle).await?;
+    filter_by_semver_range(&releases, &range)
+}
+
+#[tauri::command]
+/// retrieve the newest compatable github release
+pub async fn fetch_latest_release(device: ConnectedDevice, handle: AppHandle) -> Result<Release> {
+    info!("fetching latest release from github...");
+
+    let config = github_config(&handle);
+    let repo = config.repo_for(&device.device_type)?;
+    let mut releases = get_releases(
+        &http_client(&handle)?,
+        &device,
+        &repo,
+        false,
+        false,
+        &RetryPolicy::default(),
+        &config,
+        Some(&handle.state::<GithubState>().release_etags),
+        open_cache(&handle).as_ref(),
+        ping_github(&handle).await,
+    )
+    .await?;
+
+    // github doesn't guarantee ordering, so sort by publish date (ISO 8601 sorts lexically)
+    releases.sort_by(|a, b| b.published_at.cmp(&a.published_at));
+
+    match releases.into_iter().next() {
+        Some(release) => Ok(release),
+        None => err!(Error::Other("no compatible releases found".to_string())),
+    }
+}
+
+#[derive(TS, Serialize, Clone, Debug, PartialEq)]
+#[ts(export)]
+#[serde(tag = "type")]
+pub enum UpdateStatus {
+    UpToDate,
+    UpdateAvailable {
+        latest_tag: String,
+        current_version: String,
+    },
+    UnableToDetermine,
+}
+
+#[tauri::command]
+/// compare the firmware installed on a device against the newest compatible release
+pub async fn check_for_updates(device: ConnectedDevice, handle: AppHandle) -> Result<UpdateStatus> {
+    // reading the version talks to the device over serial, so keep it off the async runtime
+    let target = device.clone();
+    let version = tauri::async_runtime::spawn_blocking(move || device_firmware_version(target))
+        .await
+        .map_err(|err| Error::USB(err.to_string()))?;
+    let current_version = match version {
+        Ok(version) => version,
+        Err(err) => {
+            info!("unable to read firmware version: {:?}", err);
+            return Ok(UpdateStatus::UnableToDetermine);
+        }
+    };
+    let latest = fetch_latest_release(device.clone(), handle.clone()).await?;
+
+    match (
+        parse_version(&current_version),
+        parse_version(&latest.tag_name),
+    ) {
+        (Some(current), Some(newest)) if newest > current => {
+            if let Err(err) = notify_update_available(&handle, &device, &latest.tag_name) {
+                error!("unable to notify about {}: {:?}", latest.tag_name, err);
             }
+            Ok(UpdateStatus::UpdateAvailable {
+                latest_tag: latest.tag_name,
+                current_version,
+            })
         }
+        (Some(_), Some(_)) => Ok(UpdateStatus::UpToDate),
+        _ => Ok(UpdateStatus::UnableToDetermine),
+    }
+}
+
+#[tauri::command]
+/// retrieve the remaining github api quota - cached for a minute so polling doesn't burn through it
+pub async fn get_github_rate_limit_status(
+    state: tauri::State<'_, GithubState>,
+    handle: AppHandle,
+) -> Result<RateLimitStatus> {
+    if let Some((fetched, status)) = state.rate_limit.read().unwrap().as_ref() {
+        if fetched.elapsed() < GITHUB_RATE_LIMIT_TTL {
+            return Ok(status.clone());
+        }
+    }
+
+    let config = state.config.read().unwrap().clone();
+    let url = format!("{}/rate_limit", config.base_url);
+    let res = http_client(&handle)?
+        .get(url)
+        .headers(build_headers(&config))
+        .send()
+        .await?;
+
+    let status = match res.status() {
+        StatusCode::OK => {
+            RateLimitStatus::from(res.json::<RateLimitResponse>().await?.resources.core)
+        }
+        status => err!(Error::Http(format!(
+            "recieved an unsupported http status code: {status}"
+        ))),
+    };
+    debug!("github rate limit: {:?}", status);
+
+    *state.rate_limit.write().unwrap() = Some((Instant::now(), status.clone()));
+    Ok(status)
+}
+
+#[tauri::command]
+/// save a github personal access token, or clear it by passing an empty string
+pub fn set_github_token(
+    token: String,
+    state: tauri::State<'_, GithubState>,
+    handle: AppHandle,
+) -> Result<()> {
+    let token = token.trim();
+    let config = GithubConfig {
+        token: (!token.is_empty()).then(|| token.to_string()),
+        ..state.config.read().unwrap().clone()
+    };
+    config.save(&handle.config())?;
+    info!("github token updated - set: {}", config.token.is_some());
+
+    // the quota depends on the token, so forget what we knew
+    *state.rate_limit.write().unwrap() = None;
+    *state.config.write().unwrap() = config;
+    Ok(())
+}
+
+/// check a user supplied api url - an empty one means github.com
+fn parse_base_url(url: &str) -> Result<String> {
+    let url = url.trim();
+    if url.is_empty() {
+        return Ok(GITHUB_API_URL.to_string());
+    }
+    if url.ends_with('/') {
+        err!(Error::Other(format!(
+            "{url} should not end with a trailing slash"
+        )))
+    }
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if parsed.scheme() == "https" => Ok(url.to_string()),
+        Ok(_) => err!(Error::Other(format!("{url} must use https"))),
+        Err(err) => err!(Error::Other(format!("{url} is not a valid url: {err}"))),
+    }
+}
+
+#[tauri::command]
+/// fetch releases from a github enterprise instance, or go back to github.com by passing an empty string
+pub fn set_github_base_url(
+    url: String,
+    state: tauri::State<'_, GithubState>,
+    handle: AppHandle,
+) -> Result<()> {
+    let config = GithubConfig {
+        base_url: parse_base_url(&url)?,
+        ..state.config.read().unwrap().clone()
+    };
+    config.save(&handle.config())?;
+    info!("using github api at {}", config.base_url);
+
+    // nothing we know about the old server applies to the new one
+    *state.rate_limit.write().unwrap() = None;
+    *state.org_repos.write().unwrap() = None;
+    state.release_etags.clear();
+    *state.config.write().unwrap() = config;
+    Ok(())
+}
+
+#[tauri::command]
+/// list the firmware repos in the org - fetched once, then served from state for the rest of the session
+pub async fn list_github_repos(
+    state: tauri::State<'_, GithubState>,
+    handle: AppHandle,
+) -> Result<Vec<String>> {
+    if let Some(repos) = state.org_repos.read().unwrap().as_ref() {
+        return Ok(repos.clone());
+    }
+
+    let config = state.config.read().unwrap().clone();
+    let url = format!("{}/orgs/{}/repos", config.base_url, GITHUB_ORG);
+    let res = http_client(&handle)?
+        .get(url)
+        .headers(build_headers(&config))
+        .query(&Query {
+            per_page: 100,
+            page: 1,
+        })
+        .send()
+        .await?;
+
+    let mut repos: Vec<String> = match res.status() {
+        StatusCode::OK => res
+            .json::<Vec<Repository>>()
+            .await?
+            .into_iter()
+            .filter(|repo| !repo.archived && repo.name.starts_with(GITHUB_FIRMWARE_REPO_PREFIX))
+            .map(|repo| repo.name)
+            .collect(),
+        status => err!(Error::Http(format!(
+            "recieved an unsupported http status code: {status}"
+        ))),
+    };
+    repos.sort();
+    debug!("firmware repos: {:?}", repos);
+
+    *state.org_repos.write().unwrap() = Some(repos.clone());
+    Ok(repos)
+}
+
+#[tauri::command]
+/// point a device type at a different firmware repo for the rest of the session
+pub fn set_device_repo(
+    device_type: ConnectedDeviceType,
+    repo: String,
+    state: tauri::State<'_, GithubState>,
+) -> Result<()> {
+    let repo = repo.trim();
+    if repo.is_empty() {
+        err!(Error::Other("repo name cannot be empty".to_string()))
+    }
+    if let Some(repos) = state.org_repos.read().unwrap().as_ref() {
+        if !repos.iter().any(|known| known == repo) {
+            warn!("{repo} is not a known firmware repo");
+        }
+    }
+
+    info!("using {repo} for {} releases", device_type.display_name());
+    state
+        .config
+        .write()
+        .unwrap()
+        .repos
+        .insert(device_type, repo.to_string());
+    Ok(())
+}
+
+#[tauri::command]
+/// save proxy settings, and use them for every request from now on
+pub fn set_network_config(
+    config: NetworkConfig,
+    state: tauri::State<'_, NetworkState>,
+    handle: AppHandle,
+) -> Result<()> {
+    // make sure the proxy settings are usable before we keep them
+    build_http_client(&config)?;
+    config.save(&handle.config())?;
+    info!(
+        "network config updated - proxy set: {}",
+        config.proxy_url.is_some()
+    );
+
+    *state.config.write().unwrap() = config;
+    Ok(())
+}
+
+/// retrieve a single release by its tag
+pub async fn get_release_by_tag(
+    client: &reqwest::Client,
+    device_type: &ConnectedDeviceType,
+    tag: &str,
+    config: &GithubConfig,
+) -> Result<Release> {
+    let repo = config.repo_for(device_type)?;
+    let url = format!(
+        "{}/repos/{}/{}/releases/tags/{}",
+        config.base_url, GITHUB_ORG, repo, tag
+    );
+    let res = client
+        .get(url)
+        .headers(build_headers(config))
+        .send()
+        .await?;
+
+    match res.status() {
+        StatusCode::OK => Ok(res.json::<Release>().await?),
+        StatusCode::NOT_FOUND => err!(Error::Http(format!("release not found: {tag}"))),
+        status => err!(Error::Http(format!(
+            "recieved an unsupported http status code: {status}"
+        ))),
+    }
+}
+
+#[tauri::command]
+/// retrieve the release notes for a specific tag, rendered as html
+pub async fn fetch_release_notes(
+    device: ConnectedDevice,
+    tag: String,
+    handle: AppHandle,
+) -> Result<String> {
+    info!("fetching release notes for {tag} from github...");
+    let release = get_release_by_tag(
+        &http_client(&handle)?,
+        &device.device_type,
+        &tag,
+        &github_config(&handle),
+    )
+    .await?;
+
+    Ok(render_markdown(&release.body.unwrap_or_default()))
+}
+
+#[tauri::command]
+/// summarise a specific release - which devices it supports, when it went out, and its notes rendered as html
+pub async fn get_release_metadata(
+    device: ConnectedDevice,
+    tag: String,
+    handle: AppHandle,
+) -> Result<ReleaseMetadata> {
+    info!("fetching release {tag} from github...");
+    let release = get_release_by_tag(
+        &http_client(&handle)?,
+        &device.device_type,
+        &tag,
+        &github_config(&handle),
+    )
+    .await?;
+
+    Ok(ReleaseMetadata {
+        asset_count: release.asset_count(),
+        supported_devices: release.supported_devices(),
+        body_html: render_markdown(release.body.as_deref().unwrap_or_default()),
+        is_prerelease: release.prerelease,
+        published_at: release.published_at.map(|published| published.to_rfc3339()),
+        tag_name: release.tag_name,
+    })
+}
+
+/// the asset a device would install from the release tagged `tag` - the same pick `fetch_compatable_asset` makes
+fn find_release_asset(releases: &[Release], device: &ConnectedDevice, tag: &str) -> Result<Asset> {
+    let mut release = match releases.iter().find(|release| release.tag_name == tag) {
+        Some(release) => release.clone(),
+        None => err!(Error::Http(format!(
+            "no release {tag} found for {}",
+            device.display_name()
+        ))),
+    };
+    release.link_sha1_digests();
+    match release.asset_for_device(device) {
+        Some(asset) => Ok(asset.clone()),
+        None => err!(Error::Incompatable(format!(
+            "{tag} has no firmware for {}",
+            device.display_name()
+        ))),
+    }
+}
+
+#[tauri::command]
+/// the firmware a device would get from a release - name, size, and download url - without downloading it
+pub async fn fetch_asset_metadata(
+    device: ConnectedDevice,
+    release_tag: String,
+    handle: AppHandle,
+) -> Result<Asset> {
+    info!("fetching asset metadata for {release_tag}...");
+    // a specific tag was asked for, so don't hide it for being a pre-release
+    let releases = fetch_releases(device.clone(), Some(true), None, handle).await?;
+    find_release_asset(&releases, &device, &release_tag)
+}
+
+#[tauri::command]
+/// everything that changed since `from_tag`, up to the newest release - rendered as html
+pub async fn fetch_changelog_since(
+    device: ConnectedDevice,
+    from_tag: String,
+    handle: AppHandle,
+) -> Result<String> {
+    info!("fetching changelog since {from_tag} from github...");
+    let mut releases = fetch_releases(device.clone(), None, None, handle.clone()).await?;
+    releases.sort_by(|a, b| b.published_at.cmp(&a.published_at));
+
+    // the installed version may be a pre-release, which won't be in the list
+    let older = match releases.iter().find(|release| release.tag_name == from_tag) {
+        Some(release) => release.clone(),
+        None => {
+            get_release_by_tag(
+                &http_client(&handle)?,
+                &device.device_type,
+                &from_tag,
+                &github_config(&handle),
+            )
+            .await?
+        }
+    };
+    let newest = match releases.first() {
+        Some(release) => release,
+        None => err!(Error::Other("no compatible releases found".to_string())),
+    };
+
+    Ok(render_markdown(&newest.changelog_since(&older, &releases)))
+}
+
+fn render_markdown(markdown: &str) -> String {
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, pulldown_cmark::Parser::new(markdown));
+    html
+}
+
+/// a unique path in the temp dir for a downloaded file
+pub fn temp_download_path(file_name: &str) -> PathBuf {
+    // create timestamp
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    temp_dir().join(format!("{time}-{file_name}"))
+}
+
+/// when a temp download was created, if the file name is one of ours
+fn temp_download_timestamp(file_name: &str) -> Option<u128> {
+    let (time, name) = file_name.split_once('-')?;
+    let extension = name.rsplit_once('.').map(|(_, ext)| ext.to_lowercase())?;
+    match extension.as_str() {
+        "bin" | "dfu" | "uf2" => time.parse().ok(),
+        _ => None,
+    }
+}
+
+/// delete firmware downloaded to the temp dir more than `max_age` ago, returning the bytes freed
+pub fn cleanup_temp_downloads(max_age: Duration) -> Result<u64> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+
+    let mut freed: u64 = 0;
+    for entry in read_dir(temp_dir())?.flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let created = match temp_download_timestamp(&file_name) {
+            Some(created) => created,
+            None => continue,
+        };
+        if now.saturating_sub(created) < max_age.as_millis() {
+            continue;
+        }
+
+        let size = entry.metadata().map(|meta| meta.len()).unwrap_or_default();
+        match remove_file(entry.path()) {
+            Ok(_) => {
+                debug!("removed old download: {file_name}");
+                freed += size;
+            }
+            Err(err) => error!("unable to remove {file_name}: {err}"),
+        }
+    }
+    info!("cleaned up {freed} bytes of old downloads");
+    Ok(freed)
+}
+
+/// stream a download to disk chunk by chunk, reporting progress as we go
+pub async fn write_download(
+    download: ByteStream,
+    file_path: &PathBuf,
+    handle: Option<&AppHandle>,
+) -> Result<u64> {
+    info!("downloading file to: {}", file_path.display());
+    let mut file = File::create(file_path)?;
+
+    let total_bytes = download.total_bytes;
+    let mut bytes_received: u64 = 0;
+    let mut stream = download.chunks;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk)?;
+        bytes_received += chunk.len() as u64;
+
+        if let Some(handle) = handle {
+            handle
+                .emit_all(
+                    "download_progress",
+                    DownloadProgress {
+                        bytes_received,
+                        total_bytes,
+                    },
+                )
+                .unwrap();
+        }
+    }
+
+    info!(
+        "successfully downloaded - total bytes written: {}",
+        bytes_received
+    );
+    Ok(bytes_received)
+}
+
+/// retrieve specific binary asset and save to the filesystem
+pub async fn fetch_compatable_asset(
+    client: &impl GithubClient,
+    device: &ConnectedDevice,
+    mut release: Release,
+    handle: Option<&AppHandle>,
+) -> Result<PathBuf> {
+    release.link_sha1_digests();
+    let asset = match release.asset_for_device(device) {
+        Some(asset) => asset,
         None => err!(Error::Http(
             "unable to find compatible asset from release!".to_string()
         )),
+    };
+
+    // use the firmware cache when we have an app to resolve it from
+    let config = handle.map(github_config).unwrap_or_default();
+    let repo = config.repo_for(&device.device_type)?;
+    let cache = handle.and_then(open_cache);
+
+    if let Some(cached) = cache
+        .as_ref()
+        .and_then(|cache| cache.get(&repo, &release.tag_name, asset))
+    {
+        info!("using cached firmware: {}", cached.display());
+        return Ok(cached);
+    }
+
+    // fail fast when the cdn won't serve the file, rather than part way through the download
+    if !asset
+        .check_url_accessible(client, build_headers(&config))
+        .await?
+    {
+        err!(Error::Http("asset URL not accessible".to_string()))
+    }
+
+    // download the binary
+    info!(
+        "fetching asset from github: {} ({})",
+        asset.browser_download_url,
+        asset.human_readable_size()
+    );
+    let download = client
+        .fetch_bytes(&asset.browser_download_url, build_headers(&config))
+        .await?;
+
+    // write into the cache if we have one, otherwise fall back to a temp file
+    let file_path = match &cache {
+        Some(cache) => cache.prepare(&repo, &release.tag_name, asset)?,
+        None => temp_download_path(&asset.name),
+    };
+    write_download(download, &file_path, handle).await?;
+
+    // verify the download against the published checksum, if github gave us one
+    if let Some(expected) = &asset.checksum {
+        if let Err(err) = verify_checksum(&file_path, expected) {
+            error!("downloaded file failed verification: {:?}", err);
+            let _ = remove_file(&file_path);
+            return Err(err);
+        }
+    }
+
+    // the release notes can list a sha1 too - a mismatch is only reported, so the frontend can decide whether to block
+    if let Some(expected) = &asset.expected_sha1 {
+        let got = file_sha1(&file_path)?;
+        if got != *expected {
+            warn!(
+                "{} does not match the sha1 in the release notes - expected: {expected}, got: {got}",
+                asset.name
+            );
+            if let Some(handle) = handle {
+                let payload = HashMismatch {
+                    asset_name: asset.name.clone(),
+                    expected: expected.clone(),
+                    got,
+                };
+                handle.emit_all("hash_mismatch", payload).unwrap();
+            }
+        }
+    }
+
+    Ok(file_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use futures::{executor::block_on, stream, StreamExt};
+    use reqwest::header::{HeaderMap, AUTHORIZATION, IF_NONE_MATCH, USER_AGENT};
+
+    use crate::{
+        commands::github::{
+            build_headers, fetch_compatable_asset, find_release_asset, get_releases,
+            parse_base_url, temp_download_timestamp, RetryPolicy,
+        },
+        device::{ConnectedDevice, ConnectedDeviceType},
+        error::{Error, Result},
+        fixtures,
+        github::{ByteStream, GithubClient, Release, ReleasesResponse},
+        state::{GithubConfig, ReleaseEtagCache},
+        GITHUB_CLICK_REPO,
+    };
+
+    const MOCK_ETAG: &str = "\"abc123\"";
+
+    struct MockGithubClient {
+        releases: fn() -> Result<Vec<Release>>,
+        bytes: &'static [u8],
+    }
+
+    #[async_trait]
+    impl GithubClient for MockGithubClient {
+        async fn get_releases(&self, _url: &str, headers: HeaderMap) -> Result<ReleasesResponse> {
+            // behave like github - an up to date etag gets a 304
+            if headers
+                .get(IF_NONE_MATCH)
+                .map_or(false, |etag| etag == MOCK_ETAG)
+            {
+                return Ok(ReleasesResponse::NotModified);
+            }
+            (self.releases)().map(|releases| ReleasesResponse::Releases {
+                releases,
+                etag: Some(MOCK_ETAG.to_string()),
+            })
+        }
+
+        async fn fetch_bytes(&self, _url: &str, _headers: HeaderMap) -> Result<ByteStream> {
+            Ok(ByteStream {
+                total_bytes: Some(self.bytes.len() as u64),
+                chunks: stream::iter(vec![Ok(Bytes::from_static(self.bytes))]).boxed(),
+            })
+        }
+
+        async fn is_accessible(&self, _url: &str, _headers: HeaderMap) -> Result<bool> {
+            Ok(true)
+        }
+    }
+
+    fn mock_release(tag: &str, prerelease: bool, asset_name: &str) -> Release {
+        mock_release_with_draft(tag, prerelease, false, asset_name)
+    }
+
+    fn mock_release_with_draft(
+        tag: &str,
+        prerelease: bool,
+        draft: bool,
+        asset_name: &str,
+    ) -> Release {
+        let mut release = fixtures::mock_release(tag, "2023-01-01T00:00:00Z");
+        release.prerelease = prerelease;
+        release.draft = draft;
+        release.assets = vec![fixtures::mock_asset(asset_name)];
+        release
+    }
+
+    fn mock_releases() -> Result<Vec<Release>> {
+        Ok(vec![
+            mock_release("v1.0.0", false, "click_v1.0.0.0.uf2"),
+            mock_release("v1.1.0", true, "click_v1.1.0.0.uf2"),
+            mock_release("v1.0.1", false, "uloop_v1.0.1.0.uf2"),
+            mock_release_with_draft("v1.2.0", false, true, "click_v1.2.0.0.uf2"),
+        ])
+    }
+
+    fn mock_device() -> ConnectedDevice {
+        ConnectedDevice {
+            id: String::from("test"),
+            releases: None,
+            vendor_id: 0,
+            product_id: 0,
+            description: Some(String::from("CLiCK")),
+            serial_number: Some(String::from("test")),
+            device_type: ConnectedDeviceType::Click,
+            device_details: None,
+        }
+    }
+
+    fn no_retries() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 0,
+            max_wait_secs: 0,
+        }
+    }
+
+    #[test]
+    fn filters_releases() {
+        let client = MockGithubClient {
+            releases: mock_releases,
+            bytes: &[],
+        };
+        let device = mock_device();
+        let config = GithubConfig::default();
+
+        // drafts, pre-releases and other devices are skipped
+        let releases = block_on(get_releases(
+            &client,
+            &device,
+            GITHUB_CLICK_REPO,
+            false,
+            false,
+            &no_retries(),
+            &config,
+            None,
+            None,
+            true,
+        ))
+        .unwrap();
+        let tags: Vec<&str> = releases.iter().map(|r| r.tag_name.as_str()).collect();
+        assert_eq!(tags, vec!["v1.0.0"]);
+
+        // unless we opt into pre-releases
+        let releases = block_on(get_releases(
+            &client,
+            &device,
+            GITHUB_CLICK_REPO,
+            true,
+            false,
+            &no_retries(),
+            &config,
+            None,
+            None,
+            true,
+        ))
+        .unwrap();
+        assert_eq!(releases.len(), 2);
+
+        // or drafts
+        let releases = block_on(get_releases(
+            &client,
+            &device,
+            GITHUB_CLICK_REPO,
+            false,
+            true,
+            &no_retries(),
+            &config,
+            None,
+            None,
+            true,
+        ))
+        .unwrap();
+        let tags: Vec<&str> = releases.iter().map(|r| r.tag_name.as_str()).collect();
+        assert_eq!(tags, vec!["v1.0.0", "v1.2.0"]);
+    }
+
+    #[test]
+    fn finds_release_assets() {
+        let releases = mock_releases().unwrap();
+        let device = mock_device();
+
+        let asset = find_release_asset(&releases, &device, "v1.1.0").unwrap();
+        assert_eq!(asset.name, "click_v1.1.0.0.uf2");
+        assert!(matches!(
+            find_release_asset(&releases, &device, "v1.0.1"),
+            Err(Error::Incompatable(_))
+        ));
+        assert!(matches!(
+            find_release_asset(&releases, &device, "v9.9.9"),
+            Err(Error::Http(_))
+        ));
+    }
+
+    #[test]
+    fn reuses_unmodified_releases() {
+        let device = mock_device();
+        let config = GithubConfig::default();
+        let etags = ReleaseEtagCache::default();
+
+        // the first request fills the cache
+        let client = MockGithubClient {
+            releases: mock_releases,
+            bytes: &[],
+        };
+        let fetch = |client: &MockGithubClient| {
+            block_on(get_releases(
+                client,
+                &device,
+                GITHUB_CLICK_REPO,
+                false,
+                false,
+                &no_retries(),
+                &config,
+                Some(&etags),
+                None,
+                true,
+            ))
+        };
+        assert_eq!(fetch(&client).unwrap().len(), 1);
+
+        // after that a 304 is answered from the cache, without github sending the releases again
+        let client = MockGithubClient {
+            releases: || Err(Error::Http(String::from("releases were fetched again"))),
+            bytes: &[],
+        };
+        assert_eq!(fetch(&client).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn maps_errors() {
+        let device = mock_device();
+        let config = GithubConfig::default();
+
+        // offline without a cache to fall back to
+        let client = MockGithubClient {
+            releases: || Err(Error::Offline(String::from("no route to host"))),
+            bytes: &[],
+        };
+        let result = block_on(get_releases(
+            &client,
+            &device,
+            GITHUB_CLICK_REPO,
+            false,
+            false,
+            &no_retries(),
+            &config,
+            None,
+            None,
+            true,
+        ));
+        assert!(matches!(result, Err(Error::Offline(_))));
+
+        // rate limited, once we're out of retries
+        let client = MockGithubClient {
+            releases: || Err(Error::RateLimited { reset_at: None }),
+            bytes: &[],
+        };
+        let result = block_on(get_releases(
+            &client,
+            &device,
+            GITHUB_CLICK_REPO,
+            false,
+            false,
+            &no_retries(),
+            &config,
+            None,
+            None,
+            true,
+        ));
+        assert!(matches!(result, Err(Error::RateLimited { .. })));
+    }
+
+    #[test]
+    fn validates_base_urls() {
+        assert_eq!(parse_base_url("").unwrap(), "https://api.github.com");
+        assert_eq!(
+            parse_base_url(" https://github.example.com/api/v3 ").unwrap(),
+            "https://github.example.com/api/v3"
+        );
+        assert!(parse_base_url("https://github.example.com/api/v3/").is_err());
+        assert!(parse_base_url("http://github.example.com/api/v3").is_err());
+        assert!(parse_base_url("github.example.com").is_err());
+    }
+
+    #[test]
+    fn builds_headers() {
+        let headers = build_headers(&GithubConfig {
+            token: Some(String::from("abc123")),
+            ..Default::default()
+        });
+        assert_eq!(headers.get(USER_AGENT).unwrap(), "reqwest");
+        assert_eq!(headers.get(AUTHORIZATION).unwrap(), "Bearer abc123");
+    }
+
+    #[test]
+    fn downloads_assets() {
+        let client = MockGithubClient {
+            releases: mock_releases,
+            bytes: b"uf2!",
+        };
+        let release = mock_release("v1.0.0", false, "click_v1.0.0.0.uf2");
+
+        let path = block_on(fetch_compatable_asset(
+            &client,
+            &mock_device(),
+            release,
+            None,
+        ))
+        .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"uf2!");
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn recognises_temp_downloads() {
+        assert_eq!(
+            temp_download_timestamp("1700000000000-bridge6_v1.2.1.1.bin"),
+            Some(1700000000000)
+        );
+        assert_eq!(
+            temp_download_timestamp("1700000000000-click_v1.0.0.0.UF2"),
+            Some(1700000000000)
+        );
+        assert_eq!(temp_download_timestamp("1700000000000-notes.txt"), None);
+        assert_eq!(temp_download_timestamp("some-other-app.dfu"), None);
     }
 }
@@ -0,0 +1,28 @@
+use crate::{
+    error::Result,
+    history::{self, InstallRecord, InstallTelemetry},
+};
+
+#[tauri::command]
+/// past install attempts, newest first - optionally filtered to a single device
+pub fn get_install_history(
+    device_serial: Option<String>,
+    handle: tauri::AppHandle,
+) -> Result<Vec<InstallRecord>> {
+    history::load(&handle, device_serial.as_deref())
+}
+
+#[tauri::command]
+/// how long each stage of a successful bridge install took - `install_id` is the id of its history record
+pub fn get_install_telemetry(
+    install_id: i64,
+    handle: tauri::AppHandle,
+) -> Result<InstallTelemetry> {
+    history::load_telemetry(&handle, install_id)
+}
+
+#[tauri::command]
+/// forget every recorded install attempt
+pub fn clear_install_history(handle: tauri::AppHandle) -> Result<()> {
+    history::clear(&handle)
+}
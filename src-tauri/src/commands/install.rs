@@ -1,12 +1,34 @@
-use tauri::api::dialog::blocking::FileDialogBuilder;
+use std::{path::PathBuf, time::Duration};
+
+use futures::{stream, StreamExt};
+use log::{error, info, warn};
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use tauri::{api::dialog::blocking::FileDialogBuilder, Manager};
+use ts_rs::TS;
 
 use crate::{
-    commands::github::fetch_compatable_asset,
+    commands::device::device_firmware_version,
+    commands::github::{
+        fetch_compatable_asset, get_release_by_tag, github_config, http_client, temp_download_path,
+        write_download,
+    },
     device::{ConnectedDevice, ConnectedDeviceType},
+    dfu::{
+        self, bridge_image_length, install_bridge, mass_erase, wait_for_dfu_device, VerifyResult,
+    },
     error::{Error, Result},
-    github::Release,
-    state::InstallState,
-    validation::is_file_compatible,
+    github::{GithubClient, Release},
+    history::{record_attempt, record_bridge_attempt},
+    rollback::LastInstall,
+    state::{DfuConfig, InstallState, InstallerState},
+    uf2::Uf2File,
+    usb::{install_bridge_devices, InstallProgress, InstallStage},
+    util::{format_bytes, total_download_size},
+    validation::{inspect_firmware_file, is_file_compatible, FirmwareFileInfo},
+    version::check_downgrade,
+    BATCH_INSTALL_PARALLELISM, DFUSE_DEFAULT_ADDRESS, DFUSE_FLASH_END, DFU_RECOVERY_TIMEOUT,
+    DFU_WAIT_TIMEOUT, DOWNGRADE_CHECK_TIMEOUT,
 };
 
 // COMMANDS
@@ -14,6 +36,7 @@ use crate::{
 #[tauri::command]
 pub async fn local_binary(
     device: ConnectedDevice,
+    verify: Option<bool>,
     state: tauri::State<'_, InstallState>,
     handle: tauri::AppHandle,
 ) -> Result<()> {
@@ -34,7 +57,13 @@ pub async fn local_binary(
             // VERIFY COMPATIBILITY - also allow diag installs for rpi devices
             if is_file_compatible(&device, &file_path, true) {
                 state
-                    .bootloader_transition(device, file_path, &handle)
+                    .bootloader_transition(
+                        device,
+                        file_path,
+                        verify.unwrap_or(false),
+                        None,
+                        &handle,
+                    )
                     .unwrap();
 
                 Ok(())
@@ -50,12 +79,23 @@ pub async fn local_binary(
 pub async fn remote_binary(
     device: ConnectedDevice,
     release: Release,
+    verify: Option<bool>,
+    allow_downgrade: Option<bool>,
     state: tauri::State<'_, InstallState>,
     handle: tauri::AppHandle,
 ) -> Result<()> {
+    check_device_downgrade(&device, &release.tag_name, allow_downgrade.unwrap_or(false)).await?;
+
     // retrieve the remote binary
-    match fetch_compatable_asset(&device, release).await {
-        Ok(file_path) => state.bootloader_transition(device, file_path, &handle),
+    let release_tag = Some(release.tag_name.clone());
+    match fetch_compatable_asset(&http_client(&handle)?, &device, release, Some(&handle)).await {
+        Ok(file_path) => state.bootloader_transition(
+            device,
+            file_path,
+            verify.unwrap_or(false),
+            release_tag,
+            &handle,
+        ),
         Err(err) => err!(Error::Other(format!(
             "unable to retrieve asset: {:?}",
             err.to_string()
@@ -63,6 +103,652 @@ pub async fn remote_binary(
     }
 }
 
+#[tauri::command]
+pub async fn install_bridge_local(
+    device: ConnectedDevice,
+    path: String,
+    verify: Option<bool>,
+    state: tauri::State<'_, InstallState>,
+    handle: tauri::AppHandle,
+) -> Result<()> {
+    let file_path = PathBuf::from(path);
+    let verify = verify.unwrap_or(false);
+
+    // only accept dfu images or raw binaries that exist on disk
+    let extension = file_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase());
+    match extension.as_deref() {
+        Some("dfu") | Some("bin") => (),
+        _ => err!(Error::Incompatable(
+            "firmware file must be a .dfu or .bin file".to_string()
+        )),
+    }
+    if !file_path.is_file() {
+        err!(Error::IO(format!(
+            "firmware file does not exist: {}",
+            file_path.display()
+        )))
+    }
+
+    install_bridge_file(device, file_path, verify, &state, &handle).await
+}
+
+#[tauri::command]
+/// erase a bridge's entire flash before a clean install.
+/// this is destructive, so the device's serial number has to be passed back as `confirm` to go ahead.
+pub async fn wipe_device(device: ConnectedDevice, confirm: String) -> Result<()> {
+    let serial = match &device.serial_number {
+        Some(serial) => serial.clone(),
+        None => err!(Error::USB(
+            "device has no serial number to confirm against".to_string()
+        )),
+    };
+    if confirm != serial {
+        err!(Error::Other(
+            "confirmation does not match the device serial number".to_string()
+        ))
+    }
+    match device.device_type {
+        ConnectedDeviceType::Bridge4
+        | ConnectedDeviceType::Bridge6
+        | ConnectedDeviceType::BridgeBootloader => (),
+        _ => err!(Error::Incompatable(
+            "wiping is only supported on bridge devices".to_string()
+        )),
+    }
+    warn!("wiping flash on {}", device.display_name());
+
+    // erasing blocks for several seconds, so keep it off the async runtime
+    tauri::async_runtime::spawn_blocking(move || {
+        if device.device_type != ConnectedDeviceType::BridgeBootloader {
+            device.enter_bootloader()?;
+            wait_for_dfu_device(Some(&serial), Duration::from_secs(DFU_WAIT_TIMEOUT))?;
+        }
+        mass_erase(Some(&serial))
+    })
+    .await
+    .map_err(|err| Error::USB(err.to_string()))?
+}
+
+#[tauri::command]
+/// flash a bridge from a local file and read the whole image back, reporting which blocks (if any) didn't match
+pub async fn install_bridge_with_verify(
+    device: ConnectedDevice,
+    path: String,
+    state: tauri::State<'_, InstallState>,
+    handle: tauri::AppHandle,
+) -> Result<VerifyResult> {
+    match device.device_type {
+        ConnectedDeviceType::Bridge4
+        | ConnectedDeviceType::Bridge6
+        | ConnectedDeviceType::BridgeBootloader => (),
+        _ => err!(Error::Incompatable(
+            "verified installs are only supported on bridge devices".to_string()
+        )),
+    }
+    let binary = PathBuf::from(path);
+    if !binary.is_file() {
+        err!(Error::IO(format!(
+            "firmware file does not exist: {}",
+            binary.display()
+        )))
+    }
+
+    state.reset_cancel();
+    let cancelled = state.cancelled.clone();
+    let address = *handle.state::<DfuConfig>().address.read().unwrap();
+    let emitter = handle.app_handle();
+    let result_device = device.clone();
+
+    // flashing and reading back both block, so keep them off the async runtime
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let serial = device.serial_number.clone();
+        let total_bytes = bridge_image_length(&binary)?;
+        if device.device_type != ConnectedDeviceType::BridgeBootloader {
+            device.enter_bootloader()?;
+            wait_for_dfu_device(serial.as_deref(), Duration::from_secs(DFU_WAIT_TIMEOUT))?;
+        }
+        InstallProgress::new(InstallStage::Preparing, 0, total_bytes).emit(&emitter);
+
+        let progress_emitter = emitter.app_handle();
+        let mut written_bytes: u64 = 0;
+        let progress_handler = move |copied_bytes: usize| {
+            written_bytes += copied_bytes as u64;
+            InstallProgress::new(InstallStage::Writing, written_bytes, total_bytes)
+                .emit(&progress_emitter);
+        };
+        let mut verified_bytes: u64 = 0;
+        let verify_handler = |read_bytes: usize| {
+            verified_bytes += read_bytes as u64;
+            InstallProgress::new(InstallStage::Verifying, verified_bytes, total_bytes)
+                .emit(&emitter);
+        };
+
+        let result = dfu::install_bridge_with_verify(
+            binary,
+            serial,
+            address,
+            cancelled,
+            progress_handler,
+            verify_handler,
+        )?;
+        InstallProgress::new(InstallStage::Resetting, total_bytes, total_bytes).emit(&emitter);
+        Ok(result)
+    })
+    .await
+    .map_err(|err| Error::Install(err.to_string()))?;
+
+    // mismatches are still returned in full, but the history should show the install failed
+    let mismatch = result
+        .as_ref()
+        .ok()
+        .and_then(|verify| verify.mismatched_addresses.first().copied());
+    match mismatch {
+        Some(address) => record_attempt(
+            &handle,
+            &result_device,
+            None,
+            &Err::<VerifyResult, _>(Error::VerificationFailed { address }),
+        ),
+        None => record_attempt(&handle, &result_device, None, &result),
+    };
+    result
+}
+
+#[derive(TS, Serialize, Clone, Debug, PartialEq)]
+#[ts(export)]
+pub enum RecoveryStage {
+    WaitingForDevice,
+    Flashing,
+    Recovered,
+    Failed,
+}
+
+#[tauri::command]
+/// retry a bridge flash that failed part way through.
+/// a half flashed bridge usually resets back into dfu mode by itself, so wait for it to show up and flash it again from the start.
+pub async fn recover_bridge(
+    device: ConnectedDevice,
+    binary_path: String,
+    state: tauri::State<'_, InstallState>,
+    handle: tauri::AppHandle,
+) -> Result<()> {
+    match device.device_type {
+        ConnectedDeviceType::Bridge4
+        | ConnectedDeviceType::Bridge6
+        | ConnectedDeviceType::BridgeBootloader => (),
+        _ => err!(Error::Incompatable(
+            "recovery is only supported on bridge devices".to_string()
+        )),
+    }
+    let binary = PathBuf::from(binary_path);
+    if !binary.exists() {
+        err!(Error::IO(format!("{} does not exist", binary.display())))
+    }
+    state.reset_cancel();
+
+    // waiting and flashing both block, so keep them off the async runtime
+    let emitter = handle.app_handle();
+    let serial = device.serial_number.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        emitter
+            .emit_all("recovery_progress", RecoveryStage::WaitingForDevice)
+            .unwrap();
+        wait_for_dfu_device(serial.as_deref(), Duration::from_secs(DFU_RECOVERY_TIMEOUT))?;
+
+        emitter
+            .emit_all("recovery_progress", RecoveryStage::Flashing)
+            .unwrap();
+        install_bridge_devices(emitter, &binary, serial.as_deref(), false)
+    })
+    .await
+    .map_err(|err| Error::Install(err.to_string()))
+    .and_then(|result| result)
+    .map_err(|err| match err {
+        Error::Cancelled => Error::Cancelled,
+        err => Error::RecoveryFailed(err.to_string()),
+    });
+    record_bridge_attempt(&handle, &device, None, &result);
+
+    let stage = match &result {
+        Ok(_) => RecoveryStage::Recovered,
+        Err(err) => {
+            error!("recovery failed: {:?}", err);
+            RecoveryStage::Failed
+        }
+    };
+    handle.emit_all("recovery_progress", stage).unwrap();
+    result.map(|_| ())
+}
+
+#[tauri::command]
+/// roughly how long installing a release will take, in seconds - so nobody gives up on a slow flash
+pub fn get_install_estimate(device: ConnectedDevice, release: Release) -> Result<f64> {
+    match release.asset_for_device(&device) {
+        Some(asset) => Ok(asset.estimated_flash_time_secs()),
+        None => err!(Error::Incompatable(
+            "release has no asset compatible with this device".to_string()
+        )),
+    }
+}
+
+#[tauri::command]
+/// install a UF2 image that's already on disk onto a CLiCK
+pub async fn install_click_local(
+    device: ConnectedDevice,
+    path: String,
+    state: tauri::State<'_, InstallState>,
+    handle: tauri::AppHandle,
+) -> Result<()> {
+    match &device.device_type {
+        ConnectedDeviceType::Click => (),
+        _ => err!(Error::Incompatable(
+            "this install is only supported on CLiCK devices".to_string()
+        )),
+    }
+    let file_path = local_uf2_file(path)?;
+
+    // the usb listener installs onto the CLiCK's uf2 disk once it's in the bootloader
+    state.bootloader_transition(device, file_path, false, None, &handle)
+}
+
+#[tauri::command]
+/// install a UF2 image that's already on disk onto a uLoop - like CLiCK, it's RP2040 based and flashed over UF2
+pub async fn install_uloop_local(
+    device: ConnectedDevice,
+    path: String,
+    state: tauri::State<'_, InstallState>,
+    handle: tauri::AppHandle,
+) -> Result<()> {
+    match &device.device_type {
+        ConnectedDeviceType::ULoop => (),
+        _ => err!(Error::Incompatable(
+            "this install is only supported on uLoop devices".to_string()
+        )),
+    }
+    let file_path = local_uf2_file(path)?;
+
+    // the usb listener installs onto the uLoop's uf2 disk (`ULOOP_DISK_NAME`) once it's in the bootloader
+    state.bootloader_transition(device, file_path, false, None, &handle)
+}
+
+/// check a local UF2 image exists and parses, so a bad image never makes it as far as the bootloader
+fn local_uf2_file(path: String) -> Result<PathBuf> {
+    let file_path = PathBuf::from(path);
+    let is_uf2 = file_path
+        .extension()
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("uf2"));
+    if !is_uf2 {
+        err!(Error::Incompatable(
+            "firmware file must be a .uf2 file".to_string()
+        ))
+    }
+    if !file_path.is_file() {
+        err!(Error::IO(format!(
+            "firmware file does not exist: {}",
+            file_path.display()
+        )))
+    }
+    Uf2File::from_path(&file_path)?;
+    Ok(file_path)
+}
+
+/// stop a release replacing newer firmware by accident - when the installed version can't be read, there's nothing to compare.
+/// only bridges report their version outside of the bootloader, so nothing else is checked.
+async fn check_device_downgrade(
+    device: &ConnectedDevice,
+    tag: &str,
+    allow_downgrade: bool,
+) -> Result<()> {
+    match device.device_type {
+        ConnectedDeviceType::Bridge4 | ConnectedDeviceType::Bridge6 => (),
+        _ => return Ok(()),
+    }
+
+    // the version is read over serial, so keep it off the async runtime - and don't hold the install up for long
+    let mut target = device.clone();
+    let version = tauri::async_runtime::spawn_blocking(move || {
+        if target.device_details.is_none() {
+            target.try_get_device_details_within(DOWNGRADE_CHECK_TIMEOUT)?;
+        }
+        device_firmware_version(target)
+    })
+    .await
+    .map_err(|err| Error::USB(err.to_string()))?;
+
+    match version {
+        Ok(current) => check_downgrade(&current, tag, allow_downgrade),
+        Err(err) => {
+            info!(
+                "unable to read firmware version, skipping downgrade check: {:?}",
+                err
+            );
+            Ok(())
+        }
+    }
+}
+
+/// install a firmware file that's already on disk onto a bridge
+async fn install_bridge_file(
+    device: ConnectedDevice,
+    file_path: PathBuf,
+    verify: bool,
+    state: &InstallState,
+    handle: &tauri::AppHandle,
+) -> Result<()> {
+    match &device.device_type {
+        // already in DFU mode - there won't be a reconnect event, so install right away
+        ConnectedDeviceType::BridgeBootloader => {
+            state.reset_cancel();
+            // flashing blocks for a while, so keep it off the async runtime
+            let emitter = handle.app_handle();
+            let serial = device.serial_number.clone();
+            let result = tauri::async_runtime::spawn_blocking(move || {
+                install_bridge_devices(emitter, &file_path, serial.as_deref(), verify)
+            })
+            .await
+            .map_err(|err| Error::Install(err.to_string()))
+            .and_then(|result| result);
+            record_bridge_attempt(handle, &device, None, &result);
+            result.map(|_| ())
+        }
+        // otherwise enter the bootloader and let the usb listener pick up the install
+        ConnectedDeviceType::Bridge6 | ConnectedDeviceType::Bridge4 => {
+            state.bootloader_transition(device, file_path, verify, None, handle)
+        }
+        _ => err!(Error::Incompatable(
+            "local firmware installs are only supported on bridge devices".to_string()
+        )),
+    }
+}
+
+#[tauri::command]
+/// download firmware from a direct link (e.g. a ci artifact or a shared file) and install it
+pub async fn install_from_url(
+    device: ConnectedDevice,
+    url: String,
+    verify: Option<bool>,
+    state: tauri::State<'_, InstallState>,
+    handle: tauri::AppHandle,
+) -> Result<()> {
+    let parsed =
+        reqwest::Url::parse(&url).map_err(|err| Error::Http(format!("invalid url: {err}")))?;
+    if parsed.scheme() != "https" {
+        err!(Error::Http("firmware urls must use https".to_string()))
+    }
+
+    // the file name (and so the firmware format) comes from the last path segment
+    let file_name = match parsed.path_segments().and_then(|segments| segments.last()) {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => err!(Error::Http(
+            "unable to determine a file name from the url".to_string()
+        )),
+    };
+    let extension = PathBuf::from(&file_name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase());
+    match (&device.device_type, extension.as_deref()) {
+        (
+            ConnectedDeviceType::Bridge6
+            | ConnectedDeviceType::Bridge4
+            | ConnectedDeviceType::BridgeBootloader,
+            Some("dfu") | Some("bin"),
+        ) => (),
+        (ConnectedDeviceType::Click | ConnectedDeviceType::ULoop, Some("uf2")) => (),
+        (device_type, _) => err!(Error::Incompatable(format!(
+            "{file_name} can't be installed on a {:?}",
+            device_type
+        ))),
+    }
+
+    // no github headers here - this could be any host
+    info!("fetching firmware from: {url}");
+    let download = http_client(&handle)?
+        .fetch_bytes(&url, HeaderMap::new())
+        .await?;
+    let binary = temp_download_path(&file_name);
+    write_download(download, &binary, Some(&handle)).await?;
+
+    let verify = verify.unwrap_or(false);
+    match &device.device_type {
+        ConnectedDeviceType::Click | ConnectedDeviceType::ULoop => {
+            state.bootloader_transition(device, binary, verify, None, &handle)
+        }
+        _ => install_bridge_file(device, binary, verify, &state, &handle).await,
+    }
+}
+
+#[tauri::command]
+/// check a local firmware file before trying to install it
+pub fn validate_firmware_file(device: ConnectedDevice, path: String) -> Result<FirmwareFileInfo> {
+    inspect_firmware_file(&device, &PathBuf::from(path))
+}
+
+#[tauri::command]
+/// override the base address bridge firmware is flashed to.
+/// this is for advanced users with modified hardware only - a wrong address will brick the device!
+pub fn set_dfu_address(address: u32, config: tauri::State<'_, DfuConfig>) -> Result<()> {
+    if address % 4 != 0 {
+        err!(Error::Other(format!(
+            "dfu address must be 4 byte aligned: {address:#010x}"
+        )))
+    }
+    if !(DFUSE_DEFAULT_ADDRESS..=DFUSE_FLASH_END).contains(&address) {
+        err!(Error::Other(format!(
+            "dfu address must be within flash ({DFUSE_DEFAULT_ADDRESS:#010x}-{DFUSE_FLASH_END:#010x}): {address:#010x}"
+        )))
+    }
+
+    warn!("dfu address overridden: {address:#010x}");
+    *config.address.write().unwrap() = address;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn cancel_install(
+    state: tauri::State<'_, InstallState>,
+    handle: tauri::AppHandle,
+) -> Result<()> {
+    state.cancel();
+
+    // if we're still waiting on the bootloader there's nothing in flight, so go straight back
+    let waiting = matches!(
+        *state.current_state.read().unwrap(),
+        InstallerState::Bootloader { .. }
+    );
+    if waiting {
+        state.init_transition(&handle)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn rollback_firmware(
+    device: ConnectedDevice,
+    state: tauri::State<'_, InstallState>,
+    handle: tauri::AppHandle,
+) -> Result<()> {
+    let serial_number = device.serial_number.clone().unwrap_or_default();
+    let previous = match LastInstall::load(&handle)?.previous(&serial_number) {
+        Some(record) => record.clone(),
+        None => err!(Error::Other(
+            "no previous firmware version found for rollback".to_string()
+        )),
+    };
+    info!("rolling back to: {:?}", previous);
+
+    // prefer the binary we already have, otherwise download that release again
+    let binary = if previous.binary.is_file() {
+        previous.binary
+    } else {
+        match &previous.release_tag {
+            Some(tag) => {
+                let release = get_release_by_tag(
+                    &http_client(&handle)?,
+                    &device.device_type,
+                    tag,
+                    &github_config(&handle),
+                )
+                .await?;
+                fetch_compatable_asset(&http_client(&handle)?, &device, release, Some(&handle))
+                    .await?
+            }
+            None => err!(Error::Other(
+                "previous firmware file no longer exists".to_string()
+            )),
+        }
+    };
+
+    state.bootloader_transition(device, binary, false, previous.release_tag, &handle)
+}
+
+#[derive(TS, Serialize, Clone, Debug)]
+#[ts(export)]
+pub struct InstallResult {
+    device_serial: String,
+    device_name: String,
+    success: bool,
+    error: Option<String>,
+}
+
+#[derive(TS, Serialize, Clone, Debug)]
+#[ts(export)]
+pub struct BatchInstallProgress {
+    device_serial: String,
+    progress: InstallProgress,
+}
+
+/// download, enter the bootloader, and flash a single bridge as part of a batch
+async fn batch_install_device(
+    device: ConnectedDevice,
+    release: Release,
+    allow_downgrade: bool,
+    handle: tauri::AppHandle,
+) -> Result<()> {
+    match device.device_type {
+        ConnectedDeviceType::Bridge6 | ConnectedDeviceType::Bridge4 => (),
+        _ => err!(Error::Incompatable(
+            "batch installs are only supported on bridge devices".to_string()
+        )),
+    }
+    check_device_downgrade(&device, &release.tag_name, allow_downgrade).await?;
+
+    let binary =
+        fetch_compatable_asset(&http_client(&handle)?, &device, release, Some(&handle)).await?;
+    let cancelled = handle.state::<InstallState>().cancelled.clone();
+    let address = *handle.state::<DfuConfig>().address.read().unwrap();
+
+    // flashing blocks, so give each device its own thread
+    tauri::async_runtime::spawn_blocking(move || {
+        let device_serial = device.serial_number.clone();
+        let total_bytes = bridge_image_length(&binary)?;
+        let mut total_copied_bytes: u64 = 0;
+
+        device.enter_bootloader()?;
+        wait_for_dfu_device(
+            device_serial.as_deref(),
+            Duration::from_secs(DFU_WAIT_TIMEOUT),
+        )?;
+
+        let emitter = handle.app_handle();
+        let progress_serial = device_serial.clone().unwrap_or_default();
+        let progress_handler = move |copied_bytes: usize| {
+            total_copied_bytes += copied_bytes as u64;
+            emitter
+                .emit_all(
+                    "batch_install_progress",
+                    BatchInstallProgress {
+                        device_serial: progress_serial.clone(),
+                        progress: InstallProgress::new(
+                            InstallStage::Writing,
+                            total_copied_bytes,
+                            total_bytes,
+                        ),
+                    },
+                )
+                .unwrap();
+        };
+
+        install_bridge(
+            binary,
+            device_serial,
+            false,
+            address,
+            cancelled,
+            progress_handler,
+        )
+    })
+    .await
+    .map_err(|err| Error::Install(err.to_string()))?
+}
+
+#[tauri::command]
+/// flash several bridges at once, returning the outcome for each one
+pub async fn batch_install(
+    devices: Vec<ConnectedDevice>,
+    release: Release,
+    parallelism: Option<usize>,
+    allow_downgrade: Option<bool>,
+    state: tauri::State<'_, InstallState>,
+    handle: tauri::AppHandle,
+) -> Result<Vec<InstallResult>> {
+    // each bridge is found again by serial once it's in dfu mode - without one we could flash the wrong device
+    if devices.iter().any(|device| device.serial_number.is_none()) {
+        err!(Error::Incompatable(
+            "batch installs need every device to have a serial number".to_string()
+        ))
+    }
+    state.reset_cancel();
+    let allow_downgrade = allow_downgrade.unwrap_or(false);
+    let limit = parallelism.unwrap_or(BATCH_INSTALL_PARALLELISM).max(1);
+    info!(
+        "batch installing {} ({}) on {} device(s)",
+        release.tag_name,
+        format_bytes(total_download_size(std::slice::from_ref(&release))),
+        devices.len()
+    );
+
+    let results = stream::iter(devices)
+        .map(|device| {
+            let release = release.clone();
+            let handle = handle.clone();
+            async move {
+                let device_serial = device.serial_number.clone().unwrap_or_default();
+                let device_name = device.display_name();
+                let release_tag = Some(release.tag_name.clone());
+                let result =
+                    batch_install_device(device.clone(), release, allow_downgrade, handle.clone())
+                        .await;
+                record_attempt(&handle, &device, release_tag, &result);
+                match result {
+                    Ok(_) => InstallResult {
+                        device_serial,
+                        device_name,
+                        success: true,
+                        error: None,
+                    },
+                    Err(err) => {
+                        error!("batch install failed for {device_name}: {:?}", err);
+                        InstallResult {
+                            device_serial,
+                            device_name,
+                            success: false,
+                            error: Some(err.to_string()),
+                        }
+                    }
+                }
+            }
+        })
+        .buffer_unordered(limit)
+        .collect::<Vec<InstallResult>>()
+        .await;
+
+    Ok(results)
+}
+
 #[tauri::command]
 pub fn post_install(state: tauri::State<'_, InstallState>, handle: tauri::AppHandle) -> Result<()> {
     state.init_transition(&handle)
@@ -0,0 +1,35 @@
+use tracing_log::AsTrace;
+use tracing_subscriber::filter::LevelFilter;
+
+use crate::{
+    error::{Error, Result},
+    logging::{read_last_lines, LogLevelHandle},
+    state::LogDir,
+};
+
+#[tauri::command]
+/// path of the log file currently being written to
+pub fn get_log_file_path(log_dir: tauri::State<'_, LogDir>) -> Result<String> {
+    Ok(log_dir.current_file().display().to_string())
+}
+
+#[tauri::command]
+/// the last `n` records from the current log file, as raw json lines
+pub fn read_last_log_lines(n: usize, log_dir: tauri::State<'_, LogDir>) -> Result<Vec<String>> {
+    read_last_lines(&log_dir.current_file(), n)
+}
+
+#[tauri::command]
+/// change how much is written to the log file (off, error, warn, info, debug, trace) without a restart
+pub fn set_log_level(level: String, log_level: tauri::State<'_, LogLevelHandle>) -> Result<()> {
+    log_level.set(parse_log_level(&level)?)
+}
+
+/// parse a log level name, e.g. "debug"
+pub fn parse_log_level(level: &str) -> Result<LevelFilter> {
+    let filter = level
+        .trim()
+        .parse::<log::LevelFilter>()
+        .map_err(|_| Error::Other(format!("unknown log level: {level}")))?;
+    Ok(filter.as_trace())
+}
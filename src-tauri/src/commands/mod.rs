@@ -1,2 +1,7 @@
+pub mod app;
+pub mod cache;
+pub mod device;
 pub mod github;
+pub mod history;
 pub mod install;
+pub mod logs;
@@ -0,0 +1,135 @@
+use log::{error, info};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use ts_rs::TS;
+use url::Url;
+
+use crate::{
+    commands::github::{get_release_by_tag, github_config, http_client},
+    device::{ConnectedDevice, ConnectedDeviceType},
+    error::{Error, Result},
+    github::Release,
+    state::InstallState,
+    DEEP_LINK_SCHEME,
+};
+
+/// sent with `deep_link_install_requested` - the frontend confirms with the user, then installs it like any other release
+#[derive(TS, Serialize, Clone, Debug)]
+#[ts(export)]
+pub struct DeepLinkInstall {
+    pub tag: String,
+    pub device_type: ConnectedDeviceType,
+    pub release: Release,
+    /// the first connected device of the requested type - none when it hasn't been plugged in yet
+    pub device: Option<ConnectedDevice>,
+}
+
+/// pull the release tag and device type out of a `pirate-midi://install?tag=v1.2.3&device=bridge6` link
+fn parse_install_link(link: &str) -> Result<(String, ConnectedDeviceType)> {
+    let url = Url::parse(link)
+        .map_err(|err| Error::Other(format!("invalid deep link \"{link}\": {err}")))?;
+    if url.scheme() != DEEP_LINK_SCHEME || url.host_str() != Some("install") {
+        err!(Error::Other(format!("unsupported deep link: {link}")))
+    }
+
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+    let tag = match param("tag") {
+        Some(tag) if !tag.is_empty() => tag,
+        _ => err!(Error::Other(
+            "deep link is missing a release tag".to_string()
+        )),
+    };
+    let device_type = match param("device").map(|device| device.parse::<ConnectedDeviceType>()) {
+        // only devices that install releases - not bootloaders
+        Some(Ok(
+            device_type @ (ConnectedDeviceType::Bridge6
+            | ConnectedDeviceType::Bridge4
+            | ConnectedDeviceType::Click
+            | ConnectedDeviceType::ULoop),
+        )) => device_type,
+        _ => err!(Error::Incompatable(
+            "deep link is missing a supported device".to_string()
+        )),
+    };
+    Ok((tag, device_type))
+}
+
+/// look up the release a deep link points at, and ask the frontend to confirm installing it.
+/// nothing is flashed from here - a link is just a shortcut to the release, the user still has to say yes.
+pub async fn handle_deep_link(link: String, handle: AppHandle) -> Result<()> {
+    let (tag, device_type) = parse_install_link(&link)?;
+    info!(
+        "deep link requested {tag} for {}",
+        device_type.display_name()
+    );
+
+    let release = get_release_by_tag(
+        &http_client(&handle)?,
+        &device_type,
+        &tag,
+        &github_config(&handle),
+    )
+    .await?;
+    let device = handle
+        .state::<InstallState>()
+        .devices
+        .read()
+        .unwrap()
+        .iter()
+        .find(|device| device.device_type == device_type)
+        .cloned();
+    if let Some(device) = &device {
+        if release.asset_for_device(device).is_none() {
+            err!(Error::Incompatable(format!(
+                "{tag} has no firmware for {}",
+                device.display_name()
+            )))
+        }
+    }
+
+    let payload = DeepLinkInstall {
+        tag,
+        device_type,
+        release,
+        device,
+    };
+    handle
+        .emit_all("deep_link_install_requested", payload)
+        .unwrap();
+    Ok(())
+}
+
+/// handle a deep link in the background - links arrive from the os, so there's no one to return an error to
+pub fn open_deep_link(link: String, handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        if let Err(err) = handle_deep_link(link, handle).await {
+            error!("unable to open deep link: {:?}", err);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{deep_link::parse_install_link, device::ConnectedDeviceType};
+
+    #[test]
+    fn parses_install_links() {
+        let (tag, device_type) =
+            parse_install_link("pirate-midi://install?tag=v1.2.3&device=bridge6").unwrap();
+        assert_eq!(tag, "v1.2.3");
+        assert_eq!(device_type, ConnectedDeviceType::Bridge6);
+
+        let (_, device_type) =
+            parse_install_link("pirate-midi://install?device=CLiCK&tag=v2.0.0").unwrap();
+        assert_eq!(device_type, ConnectedDeviceType::Click);
+
+        assert!(parse_install_link("https://install?tag=v1.2.3&device=bridge6").is_err());
+        assert!(parse_install_link("pirate-midi://wipe?tag=v1.2.3&device=bridge6").is_err());
+        assert!(parse_install_link("pirate-midi://install?device=bridge6").is_err());
+        assert!(parse_install_link("pirate-midi://install?tag=v1.2.3&device=toaster").is_err());
+    }
+}
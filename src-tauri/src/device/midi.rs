@@ -0,0 +1,92 @@
+use log::debug;
+use midir::MidiOutput;
+
+use crate::{
+    device::ConnectedDevice,
+    error::{Error, Result},
+};
+
+const MIDI_CLIENT_NAME: &str = "pirate-midi-updater";
+
+/// names of every midi output port the os knows about
+pub fn list_midi_ports() -> Result<Vec<String>> {
+    let output = MidiOutput::new(MIDI_CLIENT_NAME)
+        .map_err(|err| Error::Other(format!("unable to access midi: {err}")))?;
+    let names = output
+        .ports()
+        .iter()
+        .filter_map(|port| output.port_name(port).ok())
+        .collect::<Vec<String>>();
+    debug!("midi ports: {:?}", names);
+    Ok(names)
+}
+
+/// the midi output port belonging to a device
+pub fn get_midi_port_name(device: &ConnectedDevice) -> Result<String> {
+    match match_midi_port(device, &list_midi_ports()?) {
+        Some(name) => Ok(name),
+        None => err!(Error::Other(
+            "unable to find a midi port for this device".to_string()
+        )),
+    }
+}
+
+// port names vary by os (e.g. "Bridge6", "Bridge 6 MIDI 1", "Bridge6:Bridge6 MIDI 1 20:0"),
+// so compare without case or spaces - the serial wins when a port name includes it
+fn match_midi_port(device: &ConnectedDevice, port_names: &[String]) -> Option<String> {
+    let normalize = |value: &str| value.to_lowercase().replace(' ', "");
+
+    let by_serial = device.serial_number.as_deref().and_then(|serial| {
+        let serial = normalize(serial);
+        port_names
+            .iter()
+            .find(|name| normalize(name).contains(&serial))
+    });
+    let by_product = device.description.as_deref().and_then(|product| {
+        let product = normalize(product);
+        port_names
+            .iter()
+            .find(|name| normalize(name).contains(&product))
+    });
+    by_serial.or(by_product).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::device::{midi::match_midi_port, ConnectedDevice, ConnectedDeviceType};
+
+    #[test]
+    fn matches_midi_ports() {
+        let device = ConnectedDevice {
+            id: String::from("16928040556979"),
+            releases: None,
+            vendor_id: 1155,
+            product_id: 22336,
+            description: Some(String::from("Bridge 6")),
+            serial_number: Some(String::from("208133813536")),
+            device_type: ConnectedDeviceType::Bridge6,
+            device_details: None,
+        };
+
+        let ports = vec![
+            String::from("IAC Driver Bus 1"),
+            String::from("Bridge6:Bridge6 MIDI 1 20:0"),
+        ];
+        assert_eq!(
+            match_midi_port(&device, &ports).as_deref(),
+            Some("Bridge6:Bridge6 MIDI 1 20:0")
+        );
+
+        // two bridges plugged in - only the serial tells them apart
+        let ports = vec![
+            String::from("Bridge 6 (208133813000)"),
+            String::from("Bridge 6 (208133813536)"),
+        ];
+        assert_eq!(
+            match_midi_port(&device, &ports).as_deref(),
+            Some("Bridge 6 (208133813536)")
+        );
+
+        assert_eq!(match_midi_port(&device, &[String::from("uLoop")]), None);
+    }
+}
@@ -1,9 +1,11 @@
 use self::bootloader::{enter_bridge_bootloader, enter_rpi_bootloader};
 use crate::{
     commands::github::fetch_releases,
+    dfu::{read_firmware_bytes, read_usb_strings},
     error::{Error, Result},
     github::Release,
-    USB_DEFAULT_BAUD_RATE, USB_TIMEOUT,
+    DFUSE_DEFAULT_ADDRESS, FACTORY_FRESH_PROBE_LENGTH, USB_BRIDGE_PRODUCT_DFU_ID,
+    USB_BRIDGE_VENDOR_ID, USB_DEFAULT_BAUD_RATE, USB_TIMEOUT,
 };
 
 use backoff::{retry, ExponentialBackoff};
@@ -11,13 +13,20 @@ use log::{debug, error, info, trace};
 use pirate_midi_rs::{check::CheckResponse, Command, PirateMIDIDevice, Response};
 use serde::{Deserialize, Serialize};
 use serialport::{SerialPortBuilder, SerialPortType};
+use std::{
+    hash::{Hash, Hasher},
+    str::FromStr,
+    time::Duration,
+};
+use tauri::AppHandle;
 use ts_rs::TS;
 use usb_enumeration::UsbDevice;
 
 mod bootloader;
+pub mod midi;
 
 // list of the supported devices
-#[derive(Deserialize, Serialize, TS, Debug, Clone, PartialEq)]
+#[derive(Deserialize, Serialize, TS, Debug, Clone, PartialEq, Eq, Hash)]
 #[ts(export)]
 pub enum ConnectedDeviceType {
     Bridge4,
@@ -26,9 +35,87 @@ pub enum ConnectedDeviceType {
     Click,
     ULoop,
     RPBootloader,
+    #[serde(other)]
     Unknown,
 }
 
+impl ConnectedDeviceType {
+    /// map a usb product string to the device it belongs to
+    pub fn from_description(value: &str) -> Self {
+        // bridges have reported their product string in a few different ways across firmware versions
+        match value.to_lowercase().replace(' ', "").as_str() {
+            "bridge6" => return ConnectedDeviceType::Bridge6,
+            "bridge4" => return ConnectedDeviceType::Bridge4,
+            _ => (),
+        }
+
+        match value {
+            "CLiCK" | "CLiCK_Diagnostics" => ConnectedDeviceType::Click,
+            "uLoop" | "uLoop_Diagnostics" => ConnectedDeviceType::ULoop,
+            "RP2 Boot" => ConnectedDeviceType::RPBootloader,
+            "DFU in FS Mode" => ConnectedDeviceType::BridgeBootloader,
+            _ => ConnectedDeviceType::Unknown,
+        }
+    }
+
+    /// the name to show people, rather than the variant name
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            ConnectedDeviceType::Bridge4 => "Bridge 4",
+            ConnectedDeviceType::Bridge6 => "Bridge 6",
+            ConnectedDeviceType::BridgeBootloader => "Bridge (bootloader)",
+            ConnectedDeviceType::Click => "CLiCK",
+            ConnectedDeviceType::ULoop => "uLoop",
+            ConnectedDeviceType::RPBootloader => "RP2040 (bootloader)",
+            ConnectedDeviceType::Unknown => "Unknown device",
+        }
+    }
+
+    /// the bridge model named in a bootloader's product string, if it names one at all
+    pub fn bridge_model_from_product(product: &str) -> Option<Self> {
+        let product = product.to_lowercase().replace(' ', "");
+        if product.contains("bridge6") {
+            Some(ConnectedDeviceType::Bridge6)
+        } else if product.contains("bridge4") {
+            Some(ConnectedDeviceType::Bridge4)
+        } else {
+            None
+        }
+    }
+
+    /// number of footswitch channels on a bridge, or 0 for anything else
+    pub fn num_channels(&self) -> u8 {
+        match self {
+            ConnectedDeviceType::Bridge4 => 4,
+            ConnectedDeviceType::Bridge6 => 6,
+            _ => 0,
+        }
+    }
+}
+
+/// parse a device type typed by a person, e.g. "Bridge6", "bridge 6" or "ULOOP" - anything unrecognised is `Unknown`
+impl FromStr for ConnectedDeviceType {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let name: String = value
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '_' && *c != '-')
+            .collect::<String>()
+            .to_lowercase();
+        Ok(match name.as_str() {
+            "" => return Err("device type is empty".to_string()),
+            "bridge4" => ConnectedDeviceType::Bridge4,
+            "bridge6" => ConnectedDeviceType::Bridge6,
+            "bridgebootloader" => ConnectedDeviceType::BridgeBootloader,
+            "click" => ConnectedDeviceType::Click,
+            "uloop" => ConnectedDeviceType::ULoop,
+            "rpbootloader" => ConnectedDeviceType::RPBootloader,
+            _ => ConnectedDeviceType::Unknown,
+        })
+    }
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize, TS)]
 #[serde(rename_all = "camelCase")]
 #[ts(export)]
@@ -85,23 +172,49 @@ pub struct ConnectedDevice {
     pub device_details: Option<DeviceDetails>,
 }
 
+/// devices are identified by serial number, so the same device compares equal however much we know about it.
+/// without a serial we fall back to the vendor and product ids, plus the platform id - which encodes where it's plugged in.
+impl PartialEq for ConnectedDevice {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.serial_number, &other.serial_number) {
+            (Some(serial), Some(other_serial)) => serial == other_serial,
+            (None, None) => {
+                (self.vendor_id, self.product_id, &self.id)
+                    == (other.vendor_id, other.product_id, &other.id)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ConnectedDevice {}
+
+impl Hash for ConnectedDevice {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match &self.serial_number {
+            Some(serial) => serial.hash(state),
+            None => (self.vendor_id, self.product_id, &self.id).hash(state),
+        }
+    }
+}
+
 impl ConnectedDevice {
     // { id: "16926237606252", vendor_id: 11914, product_id: 61450, description: Some("RP2040"), serial_number: Some("E661343213701439") }
     // { id: "17037353476373", vendor_id: 11914, product_id: 3, description: Some("RP2 Boot"), serial_number: Some("E0C912952D54") }
     // { id: "16928040556979", vendor_id: 1155, product_id: 22336, description: Some("Bridge 6"), serial_number: Some("208133813536") }
 
+    /// the device type's display name, plus the serial number when we have one - e.g. "Bridge 6 [SN: 208133813536]"
+    pub fn display_name(&self) -> String {
+        match &self.serial_number {
+            Some(serial) => format!("{} [SN: {serial}]", self.device_type.display_name()),
+            None => self.device_type.display_name().to_string(),
+        }
+    }
+
     // FYI, this is a hack for discoverability until other devices support device API
     fn determine_device_type(device: &UsbDevice) -> ConnectedDeviceType {
         match &device.description {
-            Some(value) => match value.as_str() {
-                "Bridge 6" | "Bridge6" => ConnectedDeviceType::Bridge6,
-                "Bridge 4" | "Bridge4" => ConnectedDeviceType::Bridge4,
-                "CLiCK" | "CLiCK_Diagnostics" => ConnectedDeviceType::Click,
-                "uLoop" | "uLoop_Diagnostics" => ConnectedDeviceType::ULoop,
-                "RP2 Boot" => ConnectedDeviceType::RPBootloader,
-                "DFU in FS Mode" => ConnectedDeviceType::BridgeBootloader,
-                _ => ConnectedDeviceType::Unknown,
-            },
+            Some(value) => ConnectedDeviceType::from_description(value),
             None => ConnectedDeviceType::Unknown,
         }
     }
@@ -124,6 +237,18 @@ impl ConnectedDevice {
     }
 
     pub fn try_get_device_details(&mut self) -> Result<()> {
+        self.try_get_device_details_with(ExponentialBackoff::default())
+    }
+
+    /// like `try_get_device_details`, but stops retrying once `timeout` has passed
+    pub fn try_get_device_details_within(&mut self, timeout: Duration) -> Result<()> {
+        self.try_get_device_details_with(ExponentialBackoff {
+            max_elapsed_time: Some(timeout),
+            ..ExponentialBackoff::default()
+        })
+    }
+
+    fn try_get_device_details_with(&mut self, backoff: ExponentialBackoff) -> Result<()> {
         // find our serial port
 
         let op = || match self.get_serial_port(USB_DEFAULT_BAUD_RATE) {
@@ -149,19 +274,18 @@ impl ConnectedDevice {
             Err(err) => err!(backoff::Error::transient(err.to_string())),
         };
 
-        let backoff = ExponentialBackoff::default();
         retry(backoff, op).map_err(|err| Error::Serial(err.to_string()))
     }
 
-    pub async fn try_get_github_releases(&mut self) -> Result<()> {
-        match fetch_releases(self.clone()).await {
+    pub async fn try_get_github_releases(&mut self, handle: &AppHandle) -> Result<()> {
+        match fetch_releases(self.clone(), None, None, handle.clone()).await {
             Ok(releases) => self.releases = Some(releases),
             Err(e) => error!("unable to fetch releases from github: {:?}", e),
         }
         Ok(())
     }
 
-    pub async fn try_get_all_device_info(&mut self) -> Result<()> {
+    pub async fn try_get_all_device_info(&mut self, handle: &AppHandle) -> Result<()> {
         // get device details, then retrieve the github releases - the order of this is important!
         // only attempt to get device details for those who support the device API
         match self.device_type {
@@ -173,7 +297,40 @@ impl ConnectedDevice {
             }
             _ => (),
         }
-        self.try_get_github_releases().await
+        self.try_get_github_releases(handle).await
+    }
+
+    /// a bridge in its bootloader with nothing in flash - a new device that's never had firmware installed.
+    /// this reads from the device, so it blocks.
+    pub fn is_factory_fresh(&self) -> bool {
+        if self.device_type != ConnectedDeviceType::BridgeBootloader {
+            return false;
+        }
+        match read_firmware_bytes(
+            USB_BRIDGE_VENDOR_ID,
+            USB_BRIDGE_PRODUCT_DFU_ID,
+            DFUSE_DEFAULT_ADDRESS,
+            FACTORY_FRESH_PROBE_LENGTH,
+        ) {
+            Ok(bytes) => !bytes.is_empty() && bytes.iter().all(|&byte| byte == 0xFF),
+            Err(err) => {
+                debug!("unable to read flash to check for firmware: {:?}", err);
+                false
+            }
+        }
+    }
+
+    /// which bridge a device in its bootloader is, going by its usb strings - none when the bootloader doesn't say.
+    /// this reads from the device, so it blocks.
+    pub fn bootloader_bridge_model(&self) -> Option<ConnectedDeviceType> {
+        if self.device_type != ConnectedDeviceType::BridgeBootloader {
+            return None;
+        }
+        let strings = read_usb_strings(USB_BRIDGE_VENDOR_ID, USB_BRIDGE_PRODUCT_DFU_ID).ok()?;
+        strings
+            .product
+            .as_deref()
+            .and_then(ConnectedDeviceType::bridge_model_from_product)
     }
 
     pub fn enter_bootloader(&self) -> Result<()> {
@@ -204,3 +361,191 @@ impl From<&UsbDevice> for ConnectedDevice {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use usb_enumeration::UsbDevice;
+
+    use crate::device::{ConnectedDevice, ConnectedDeviceType};
+
+    #[test]
+    fn parses_device_types() {
+        let parses = |value: &str| value.parse::<ConnectedDeviceType>().unwrap();
+        for value in ["Bridge4", "bridge4", "BRIDGE4", "Bridge 4"] {
+            assert_eq!(parses(value), ConnectedDeviceType::Bridge4);
+        }
+        for value in ["Bridge6", "bridge 6", "bridge_6"] {
+            assert_eq!(parses(value), ConnectedDeviceType::Bridge6);
+        }
+        assert_eq!(
+            parses("Bridge Bootloader"),
+            ConnectedDeviceType::BridgeBootloader
+        );
+        assert_eq!(parses("CLiCK"), ConnectedDeviceType::Click);
+        assert_eq!(parses("uLoop"), ConnectedDeviceType::ULoop);
+        assert_eq!(parses("RP-Bootloader"), ConnectedDeviceType::RPBootloader);
+        assert_eq!(parses("Unknown"), ConnectedDeviceType::Unknown);
+        assert_eq!(parses("toaster"), ConnectedDeviceType::Unknown);
+        assert!(" ".parse::<ConnectedDeviceType>().is_err());
+    }
+
+    #[test]
+    fn finds_bridge_models_in_product_strings() {
+        assert_eq!(
+            ConnectedDeviceType::bridge_model_from_product("Bridge 6 DFU"),
+            Some(ConnectedDeviceType::Bridge6)
+        );
+        assert_eq!(
+            ConnectedDeviceType::bridge_model_from_product("BRIDGE4 Bootloader"),
+            Some(ConnectedDeviceType::Bridge4)
+        );
+        assert_eq!(
+            ConnectedDeviceType::bridge_model_from_product("DFU in FS Mode"),
+            None
+        );
+    }
+
+    #[test]
+    fn round_trips_devices() {
+        let device_types = vec![
+            ConnectedDeviceType::Bridge4,
+            ConnectedDeviceType::Bridge6,
+            ConnectedDeviceType::BridgeBootloader,
+            ConnectedDeviceType::Click,
+            ConnectedDeviceType::ULoop,
+            ConnectedDeviceType::RPBootloader,
+            ConnectedDeviceType::Unknown,
+        ];
+
+        for device_type in device_types {
+            let device = ConnectedDevice {
+                id: String::from("16928040556979"),
+                releases: None,
+                vendor_id: 1155,
+                product_id: 22336,
+                description: Some(String::from("Bridge 6")),
+                serial_number: Some(String::from("208133813536")),
+                device_type: device_type.clone(),
+                device_details: None,
+            };
+
+            let json = serde_json::to_string(&device).unwrap();
+            let parsed: ConnectedDevice = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(parsed.id, device.id);
+            assert_eq!(parsed.vendor_id, device.vendor_id);
+            assert_eq!(parsed.product_id, device.product_id);
+            assert_eq!(parsed.description, device.description);
+            assert_eq!(parsed.serial_number, device.serial_number);
+            assert_eq!(parsed.device_type, device_type);
+        }
+    }
+
+    #[test]
+    fn unknown_device_types() {
+        let parsed: ConnectedDeviceType = serde_json::from_str("\"Bridge8\"").unwrap();
+        assert_eq!(parsed, ConnectedDeviceType::Unknown);
+    }
+
+    #[test]
+    fn from_usb_device() {
+        let device = ConnectedDevice::from(&UsbDevice {
+            id: String::from("17037353476373"),
+            vendor_id: 11914,
+            product_id: 3,
+            description: Some(String::from("RP2 Boot")),
+            serial_number: Some(String::from("E0C912952D54")),
+        });
+
+        assert_eq!(device.device_type, ConnectedDeviceType::RPBootloader);
+        assert_eq!(device.serial_number.as_deref(), Some("E0C912952D54"));
+        assert!(device.releases.is_none());
+    }
+
+    #[test]
+    fn bridge_descriptions() {
+        let cases = [
+            ("Bridge 6", ConnectedDeviceType::Bridge6),
+            ("Bridge6", ConnectedDeviceType::Bridge6),
+            ("BRIDGE6", ConnectedDeviceType::Bridge6),
+            ("bridge 6", ConnectedDeviceType::Bridge6),
+            ("Bridge 4", ConnectedDeviceType::Bridge4),
+            ("BRIDGE4", ConnectedDeviceType::Bridge4),
+            ("bridge 4", ConnectedDeviceType::Bridge4),
+            ("Bridge 8", ConnectedDeviceType::Unknown),
+        ];
+
+        for (description, expected) in cases {
+            assert_eq!(ConnectedDeviceType::from_description(description), expected);
+        }
+        assert_eq!(ConnectedDeviceType::Bridge4.num_channels(), 4);
+        assert_eq!(ConnectedDeviceType::Bridge6.num_channels(), 6);
+        assert_eq!(ConnectedDeviceType::Click.num_channels(), 0);
+    }
+
+    #[test]
+    fn names_devices() {
+        let device = ConnectedDevice {
+            id: String::from("16928040556979"),
+            releases: None,
+            vendor_id: 1155,
+            product_id: 22336,
+            description: Some(String::from("Bridge 6")),
+            serial_number: Some(String::from("208133813536")),
+            device_type: ConnectedDeviceType::Bridge6,
+            device_details: None,
+        };
+        assert_eq!(device.display_name(), "Bridge 6 [SN: 208133813536]");
+
+        let device = ConnectedDevice {
+            serial_number: None,
+            device_type: ConnectedDeviceType::ULoop,
+            ..device
+        };
+        assert_eq!(device.display_name(), "uLoop");
+    }
+
+    #[test]
+    fn identifies_devices_by_serial() {
+        let device = ConnectedDevice {
+            id: String::from("16928040556979"),
+            releases: None,
+            vendor_id: 1155,
+            product_id: 22336,
+            description: Some(String::from("Bridge 6")),
+            serial_number: Some(String::from("208133813536")),
+            device_type: ConnectedDeviceType::Bridge6,
+            device_details: None,
+        };
+
+        // the same bridge, re-enumerated in the bootloader
+        let bootloader = ConnectedDevice {
+            id: String::from("16928040551234"),
+            product_id: 57105,
+            description: Some(String::from("DFU in FS Mode")),
+            device_type: ConnectedDeviceType::BridgeBootloader,
+            ..device.clone()
+        };
+        assert_eq!(device, bootloader);
+
+        let other = ConnectedDevice {
+            serial_number: Some(String::from("208133813537")),
+            ..device.clone()
+        };
+        assert_ne!(device, other);
+
+        // without a serial, only the same usb port counts
+        let anonymous = ConnectedDevice {
+            serial_number: None,
+            ..device.clone()
+        };
+        assert_ne!(device, anonymous);
+        assert_eq!(anonymous, anonymous.clone());
+
+        let devices: HashSet<ConnectedDevice> =
+            [device, bootloader, other, anonymous].into_iter().collect();
+        assert_eq!(devices.len(), 3);
+    }
+}
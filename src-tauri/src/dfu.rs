@@ -1,20 +1,217 @@
+// with the `simulation` feature the installers are swapped out, leaving some of their helpers unused
+#![cfg_attr(feature = "simulation", allow(dead_code, unused_imports))]
+
+#[cfg(feature = "simulation")]
+pub use crate::simulation::{install_bridge, install_bridge_with_verify, install_uf2_to};
 use crate::{
-    error::{Error, Result},
-    DFUSE_DEFAULT_ADDRESS, USB_BRIDGE_PRODUCT_DFU_ID, USB_BRIDGE_VENDOR_ID,
+    device::ConnectedDeviceType,
+    dfu_suffix::{parse_dfu_suffix, DFU_SUFFIX_LENGTH},
+    error::{Error, Result, UsbConnectionError},
+    uf2::{Uf2File, UF2_BLOCK_SIZE},
+    CLICK_DISK_NAME, DFUSE_DEFAULT_ADDRESS, DFUSE_FLASH_END, DFUSE_TRANSFER_SIZE,
+    DFU_ERASE_TIMEOUT, DFU_POLL_INTERVAL, LOW_BUS_POWER_MA, RPI_DISK_NAME, RPI_DISK_POLL_INTERVAL,
+    RPI_DISK_SIZE, RPI_DISK_SIZE_TOLERANCE, ULOOP_DISK_NAME, USB_BRIDGE_PRODUCT_DFU_ID,
+    USB_BRIDGE_VENDOR_ID, USB_OPEN_ATTEMPTS, USB_OPEN_RETRY_DELAY, USB_TIMEOUT,
 };
 use dfu_libusb::DfuLibusb;
-use fs_extra::file::{copy_with_progress, CopyOptions, TransitProcess};
-use log::{debug, error};
-use std::{path::PathBuf, time::Duration};
-use sysinfo::{DiskExt, RefreshKind, System, SystemExt};
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use std::{
+    ffi::OsStr,
+    fs::{read, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use sysinfo::{Disk, DiskExt, RefreshKind, System, SystemExt};
+use ts_rs::TS;
+use usb_enumeration::UsbDevice;
+
+// dfu class requests
+const DFU_REQUEST_OUT: u8 = 0x21;
+const DFU_REQUEST_IN: u8 = 0xA1;
+const DFU_DNLOAD: u8 = 1;
+const DFU_UPLOAD: u8 = 2;
+const DFU_GETSTATUS: u8 = 3;
+const DFU_ABORT: u8 = 6;
+const DFUSE_SET_ADDRESS: u8 = 0x21;
+const DFUSE_ERASE: u8 = 0x41;
+const DFUSE_FIRST_UPLOAD_BLOCK: u16 = 2;
+const DFU_STATE_DNBUSY: u8 = 4;
+const DFU_STATE_ERROR: u8 = 10;
+const DFU_INTERFACE_CLASS: u8 = 0xFE;
+const DFU_INTERFACE_SUBCLASS: u8 = 0x01;
+const DFU_PROTOCOL_DFU_MODE: u8 = 0x02;
+const DFU_FUNCTIONAL_DESCRIPTOR: u8 = 0x21;
+const USB_HUB_CLASS: u8 = 0x09;
+
+/// env var the `integration_test` feature reads the mock UF2 disk's mount point from
+#[cfg(feature = "integration_test")]
+pub const MOCK_RPI_DISK_PATH_VAR: &str = "MOCK_RPI_DISK_PATH";
+
+/// the volume label a device's UF2 bootloader mounts as
+pub fn uf2_disk_name(device_type: &ConnectedDeviceType) -> &'static str {
+    match device_type {
+        ConnectedDeviceType::Click => CLICK_DISK_NAME,
+        ConnectedDeviceType::ULoop => ULOOP_DISK_NAME,
+        // anything else we'd flash over uf2 is running the stock rp2040 bootloader
+        _ => RPI_DISK_NAME,
+    }
+}
+
+/// install a UF2 image on a CLiCK.
+/// CLiCK is RP2040 based, so it doesn't speak DFU - see `install_uf2`.
+pub fn install_click<F>(
+    binary: PathBuf,
+    timeout_secs: u64,
+    cancelled: Arc<AtomicBool>,
+    progress_handler: F,
+) -> Result<u64>
+where
+    F: FnMut(usize, usize),
+{
+    install_uf2(
+        binary,
+        CLICK_DISK_NAME,
+        timeout_secs,
+        cancelled,
+        progress_handler,
+    )
+}
 
-pub fn install_rpi<F>(binary: PathBuf, progress_handler: F) -> Result<u64>
+/// install a UF2 image on an RP2040 based device (CLiCK and uLoop).
+/// these don't speak DFU - once in the bootloader they mount a mass storage disk labelled
+/// `disk_name`, and copying the UF2 onto it flashes the device and reboots it.
+/// the progress handler is called with the number of blocks written, and the total block count.
+/// cancelling stops the copy between blocks - the bootloader flashes each block as it arrives,
+/// so a cancelled install leaves a partial image that has to be fully reinstalled.
+pub fn install_uf2<F>(
+    binary: PathBuf,
+    disk_name: &str,
+    timeout_secs: u64,
+    cancelled: Arc<AtomicBool>,
+    progress_handler: F,
+) -> Result<u64>
 where
-    F: FnMut(TransitProcess),
+    F: FnMut(usize, usize),
 {
-    // sleep to allow disk to mount
-    std::thread::sleep(Duration::from_secs(3));
+    install_uf2_to(
+        binary,
+        disk_name,
+        &|| find_uf2_disk(disk_name),
+        timeout_secs,
+        cancelled,
+        progress_handler,
+    )
+}
+
+/// install a UF2 image onto whatever mount point `disk_finder` returns - see `install_uf2`.
+/// split out so the copy can be exercised against a plain directory instead of a real device.
+#[cfg(not(feature = "simulation"))]
+pub fn install_uf2_to<F>(
+    binary: PathBuf,
+    disk_name: &str,
+    disk_finder: &dyn Fn() -> Option<PathBuf>,
+    timeout_secs: u64,
+    cancelled: Arc<AtomicBool>,
+    mut progress_handler: F,
+) -> Result<u64>
+where
+    F: FnMut(usize, usize),
+{
+    // parse up front, so a bad image never makes it to the device
+    let image = Uf2File::from_path(&binary)?;
+    let total_blocks = image.block_count();
+
+    // wait for the disk to mount
+    let mount_point = match wait_for_uf2_disk(disk_finder, Duration::from_secs(timeout_secs)) {
+        Some(mount_point) => mount_point,
+        None => err!(Error::Install(format!(
+            "UF2 disk {disk_name} did not appear within timeout"
+        ))),
+    };
+    let destination = mount_point.join(PathBuf::from(binary.file_name().unwrap()));
+
+    // make sure the whole image will fit before we start writing
+    let required = (total_blocks * UF2_BLOCK_SIZE) as u64;
+    if let Some(available) = uf2_disk_available_space(disk_name) {
+        if available < required {
+            err!(Error::Install(format!(
+                "not enough space on {disk_name} - {required} bytes needed, {available} available"
+            )))
+        }
+    }
+
+    // write the image to the device one block at a time
+    let mut file = File::create(destination)
+        .map_err(|err| Error::IO(format!("upload failed with reason: {:?}", err)))?;
+    let mut bytes_written: u64 = 0;
+    for (index, block) in image.blocks.iter().enumerate() {
+        if cancelled.load(Ordering::SeqCst) {
+            warn!(
+                "uf2 install cancelled after {index} of {total_blocks} blocks - the device needs a full reinstall"
+            );
+            err!(Error::Cancelled)
+        }
 
+        let raw = block.to_bytes();
+        file.write_all(&raw)
+            .map_err(|err| Error::IO(format!("upload failed with reason: {:?}", err)))?;
+        bytes_written += raw.len() as u64;
+
+        debug!("wrote uf2 block {} of {}", index + 1, total_blocks);
+        progress_handler(index + 1, total_blocks);
+    }
+
+    // the bootloader resets as soon as it has every block, so failing to sync isn't fatal
+    if let Err(err) = file.sync_all() {
+        debug!("unable to sync uf2 disk: {:?}", err);
+    }
+    Ok(bytes_written)
+}
+
+/// poll for a UF2 disk until it mounts or we run out of time
+pub fn wait_for_uf2_disk(
+    disk_finder: &dyn Fn() -> Option<PathBuf>,
+    timeout: Duration,
+) -> Option<PathBuf> {
+    let started = Instant::now();
+    loop {
+        if let Some(mount_point) = disk_finder() {
+            debug!("UF2 disk mounted after {:?}", started.elapsed());
+            return Some(mount_point);
+        }
+        if started.elapsed() >= timeout {
+            return None;
+        }
+        std::thread::sleep(RPI_DISK_POLL_INTERVAL);
+    }
+}
+
+/// locate the mount point of a UF2 mass storage disk, if it's mounted
+pub fn find_uf2_disk(disk_name: &str) -> Option<PathBuf> {
+    // integration tests point us at a plain directory instead of a real device
+    #[cfg(feature = "integration_test")]
+    if let Some(mount_point) = std::env::var_os(MOCK_RPI_DISK_PATH_VAR) {
+        return Some(PathBuf::from(mount_point));
+    }
+
+    with_uf2_disk(disk_name, |disk| disk.mount_point().to_path_buf())
+}
+
+/// free space on a UF2 mass storage disk, if it's mounted
+pub fn uf2_disk_available_space(disk_name: &str) -> Option<u64> {
+    with_uf2_disk(disk_name, |disk| disk.available_space())
+}
+
+fn with_uf2_disk<T, F>(disk_name: &str, f: F) -> Option<T>
+where
+    F: FnOnce(&Disk) -> T,
+{
     // get disk info from system
     let mut sys = System::new_with_specifics(RefreshKind::new().with_disks_list());
 
@@ -22,62 +219,378 @@ where
     sys.refresh_disks_list();
     sys.refresh_disks();
 
-    // brittle... but works
     let disks = sys.disks();
     debug!("available disks: {:?}", disks);
 
-    let rpi_disk = disks
+    disks
         .iter()
-        .find(|&disk| disk.is_removable() && disk.name().eq_ignore_ascii_case("RPI-RP2"));
-
-    match rpi_disk {
-        Some(disk) => {
-            let options = CopyOptions::new().buffer_size(512);
-            let destination = disk
-                .mount_point()
-                .join(PathBuf::from(binary.file_name().unwrap()));
-
-            // Copy binary file path to device
-            match copy_with_progress(binary, destination, &options, progress_handler) {
-                Ok(bytes_written) => Ok(bytes_written),
-                Err(err) => err!(Error::IO(format!("upload failed with reason: {:?}", err))),
-            }
+        .find(|&disk| {
+            is_uf2_disk_candidate(
+                disk.name(),
+                disk.is_removable(),
+                disk.total_space(),
+                disk_name,
+            )
+        })
+        .map(f)
+}
+
+/// match on the volume name, and the size so similarly named drives don't get picked up
+fn is_uf2_disk_candidate(name: &OsStr, removable: bool, total_space: u64, disk_name: &str) -> bool {
+    removable
+        && is_uf2_disk(name, disk_name)
+        && RPI_DISK_SIZE.saturating_sub(total_space) <= RPI_DISK_SIZE_TOLERANCE
+        && total_space <= RPI_DISK_SIZE
+}
+
+/// the volume shows up as e.g. "RPI-RP2", "RPI_RP2" or lowercased, depending on the os and filesystem driver
+pub fn is_uf2_disk(name: &OsStr, disk_name: &str) -> bool {
+    name.to_string_lossy()
+        .replace('_', "-")
+        .eq_ignore_ascii_case(&disk_name.replace('_', "-"))
+}
+
+/// poll until a bridge shows up in DFU mode (optionally with a specific serial number)
+pub fn wait_for_dfu_device(serial: Option<&str>, timeout: Duration) -> Result<()> {
+    let started = Instant::now();
+    loop {
+        let found =
+            usb_enumeration::enumerate(Some(USB_BRIDGE_VENDOR_ID), Some(USB_BRIDGE_PRODUCT_DFU_ID))
+                .iter()
+                .any(|device| serial.is_none() || device.serial_number.as_deref() == serial);
+
+        if found {
+            debug!("dfu device appeared after {:?}", started.elapsed());
+            return Ok(());
+        }
+        if started.elapsed() >= timeout {
+            err!(Error::USB(
+                "device did not enter dfu mode within timeout".to_string()
+            ))
         }
-        None => err!(Error::Install("UF2 disk not available".to_string())),
+        std::thread::sleep(DFU_POLL_INTERVAL);
     }
 }
 
-pub fn install_bridge<F>(binary: PathBuf, progress_handler: F) -> Result<()>
+/// poll until any device with the given vid/pid shows up - used while the user manually puts a device into its bootloader
+pub fn wait_for_bootloader_mode(vid: u16, bootloader_pid: u16, timeout_secs: u64) -> Result<()> {
+    let started = Instant::now();
+    let timeout = Duration::from_secs(timeout_secs);
+    loop {
+        if !usb_enumeration::enumerate(Some(vid), Some(bootloader_pid)).is_empty() {
+            info!(
+                "bootloader {vid:#06x}:{bootloader_pid:#06x} detected after {:?}",
+                started.elapsed()
+            );
+            return Ok(());
+        }
+        if started.elapsed() >= timeout {
+            err!(Error::Bootloader(
+                "device did not enter bootloader mode within timeout".to_string()
+            ))
+        }
+        std::thread::sleep(DFU_POLL_INTERVAL);
+    }
+}
+
+/// poll until a device with the given serial number comes back with its normal pid, e.g. after a flash
+pub fn wait_for_reconnect(serial: &str, normal_pid: u16, timeout_secs: u64) -> Result<UsbDevice> {
+    let started = Instant::now();
+    let timeout = Duration::from_secs(timeout_secs);
+    loop {
+        let found = usb_enumeration::enumerate(None, Some(normal_pid))
+            .into_iter()
+            .find(|device| device.serial_number.as_deref() == Some(serial));
+
+        if let Some(device) = found {
+            info!("{serial} reconnected after {:?}", started.elapsed());
+            return Ok(device);
+        }
+        if started.elapsed() >= timeout {
+            err!(Error::USB(format!(
+                "{serial} did not reconnect within timeout"
+            )))
+        }
+        std::thread::sleep(DFU_POLL_INTERVAL);
+    }
+}
+
+/// wraps a reader so an in-flight download can be aborted between chunks
+struct CancellableReader<R> {
+    inner: R,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<R: Read> Read for CancellableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "install cancelled",
+            ));
+        }
+        self.inner.read(buf)
+    }
+}
+
+fn is_dfu_file(binary: &Path) -> bool {
+    binary
+        .extension()
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("dfu"))
+}
+
+/// the number of bytes that actually get written to a bridge - .dfu suffixes are left off the flash
+pub fn bridge_image_length(binary: &Path) -> Result<u64> {
+    let length = binary
+        .metadata()
+        .map_err(|e| Error::IO(format!("could not read firmware file: {}", e)))?
+        .len();
+    if is_dfu_file(binary) {
+        Ok(length.saturating_sub(DFU_SUFFIX_LENGTH as u64))
+    } else {
+        Ok(length)
+    }
+}
+
+/// what came of an install with readback verification
+#[derive(TS, Serialize, Debug, Clone, PartialEq)]
+#[ts(export)]
+pub struct VerifyResult {
+    pub bytes_written: u64,
+    /// false when the readback didn't match, or the device reset before it could be read back
+    pub verified: bool,
+    /// the start of every transfer-sized block that didn't match the firmware file
+    pub mismatched_addresses: Vec<u32>,
+}
+
+#[cfg(not(feature = "simulation"))]
+pub fn install_bridge<F>(
+    binary: PathBuf,
+    serial: Option<String>,
+    verify: bool,
+    address: u32,
+    cancelled: Arc<AtomicBool>,
+    progress_handler: F,
+) -> Result<()>
+where
+    F: FnMut(usize) + 'static,
+{
+    let result = flash_bridge(
+        &LibusbContext::new()?,
+        binary,
+        serial,
+        verify,
+        address,
+        cancelled,
+        progress_handler,
+        |_| (),
+    )?;
+    match result.mismatched_addresses.first() {
+        Some(&address) => err!(Error::VerificationFailed { address }),
+        None => Ok(()),
+    }
+}
+
+/// flash a bridge and read the whole image back, reporting progress for each phase separately.
+/// unlike `install_bridge`, a mismatch isn't an error - every mismatched block is collected in the result.
+#[cfg(not(feature = "simulation"))]
+pub fn install_bridge_with_verify<F, G>(
+    binary: PathBuf,
+    serial: Option<String>,
+    address: u32,
+    cancelled: Arc<AtomicBool>,
+    progress_handler: F,
+    verify_handler: G,
+) -> Result<VerifyResult>
 where
     F: FnMut(usize) + 'static,
+    G: FnMut(usize),
+{
+    flash_bridge(
+        &LibusbContext::new()?,
+        binary,
+        serial,
+        true,
+        address,
+        cancelled,
+        progress_handler,
+        verify_handler,
+    )
+}
+
+/// a bridge in dfu mode, as the installer drives it
+pub trait DfuDevice {
+    /// write `length` bytes from `reader` to flash at `address`, reporting each chunk written to `progress`
+    fn download(
+        &mut self,
+        reader: Box<dyn Read>,
+        length: u32,
+        address: u32,
+        progress: Box<dyn FnMut(usize)>,
+    ) -> std::result::Result<(), dfu_libusb::Error>;
+    fn will_detach(&self) -> bool;
+    fn detach(&mut self) -> std::result::Result<(), dfu_libusb::Error>;
+    fn usb_reset(&mut self) -> std::result::Result<(), dfu_libusb::Error>;
+}
+
+impl<C: rusb::UsbContext> DfuDevice for dfu_libusb::Dfu<C> {
+    fn download(
+        &mut self,
+        reader: Box<dyn Read>,
+        length: u32,
+        address: u32,
+        progress: Box<dyn FnMut(usize)>,
+    ) -> std::result::Result<(), dfu_libusb::Error> {
+        self.with_progress(progress).override_address(address);
+        dfu_libusb::Dfu::download(self, reader, length)
+    }
+
+    fn will_detach(&self) -> bool {
+        dfu_libusb::Dfu::will_detach(self)
+    }
+
+    fn detach(&mut self) -> std::result::Result<(), dfu_libusb::Error> {
+        dfu_libusb::Dfu::detach(self)
+    }
+
+    fn usb_reset(&mut self) -> std::result::Result<(), dfu_libusb::Error> {
+        dfu_libusb::Dfu::usb_reset(self).map(|_| ())
+    }
+}
+
+/// where the installer finds bridges in dfu mode - lets the install flow be tested without hardware
+pub trait DfuContext {
+    /// open the bridge with `serial`, or the first one found
+    fn open(&self, serial: Option<&str>) -> Result<Box<dyn DfuDevice>>;
+    /// read `length` bytes of flash back, along with the transfer size they were read in
+    fn read_flash(
+        &self,
+        serial: Option<&str>,
+        address: u32,
+        length: usize,
+        progress: &mut dyn FnMut(usize),
+    ) -> Result<(Vec<u8>, u16)>;
+}
+
+/// bridges on the real usb bus, through libusb
+pub struct LibusbContext {
+    context: rusb::Context,
+}
+
+impl LibusbContext {
+    pub fn new() -> Result<Self> {
+        let context = rusb::Context::new()
+            .map_err(|e| Error::Install(format!("unable to create usb context: {}", e)))?;
+        Ok(LibusbContext { context })
+    }
+}
+
+impl DfuContext for LibusbContext {
+    fn open(&self, serial: Option<&str>) -> Result<Box<dyn DfuDevice>> {
+        let (device, handle) = open_device_matching(
+            &self.context,
+            USB_BRIDGE_VENDOR_ID,
+            USB_BRIDGE_PRODUCT_DFU_ID,
+            serial,
+        )?;
+
+        // build the DFU interface
+        // claiming the interface is where another process holding it shows up
+        let dfu_iface = DfuLibusb::from_usb_device(device, handle, 0, 0).map_err(|e| match e {
+            dfu_libusb::Error::LibUsb(e) => {
+                UsbConnectionError::from_libusb(e, USB_BRIDGE_VENDOR_ID).into()
+            }
+            e => Error::Install(e.to_string()),
+        })?;
+        Ok(Box::new(dfu_iface))
+    }
+
+    fn read_flash(
+        &self,
+        serial: Option<&str>,
+        address: u32,
+        length: usize,
+        progress: &mut dyn FnMut(usize),
+    ) -> Result<(Vec<u8>, u16)> {
+        let (device, mut handle) = open_device_matching(
+            &self.context,
+            USB_BRIDGE_VENDOR_ID,
+            USB_BRIDGE_PRODUCT_DFU_ID,
+            serial,
+        )?;
+        let data = upload(&device, &mut handle, address, length, progress)?;
+        Ok((data, transfer_size(&device)))
+    }
+}
+
+fn flash_bridge<F, G>(
+    context: &impl DfuContext,
+    binary: PathBuf,
+    serial: Option<String>,
+    verify: bool,
+    address: u32,
+    cancelled: Arc<AtomicBool>,
+    progress_handler: F,
+    mut verify_handler: G,
+) -> Result<VerifyResult>
+where
+    F: FnMut(usize) + 'static,
+    G: FnMut(usize),
 {
     // open the binary file
-    let file = std::fs::File::open(binary)
+    let file = std::fs::File::open(&binary)
         .map_err(|e| Error::IO(format!("could not open firmware file: {}", e)))?;
 
-    // create our USB context
-    let context = rusb::Context::new()
-        .map_err(|e| Error::Install(format!("unable to create usb context: {}", e)))?;
+    // .dfu images carry a suffix - check it before touching the device
+    if is_dfu_file(&binary) {
+        let suffix = parse_dfu_suffix(&binary)?;
+        info!(
+            "dfu image targets {:04x}:{:04x}, device version {:#06x}",
+            suffix.vendor_id, suffix.product_id, suffix.device_version
+        );
+    }
+    let length = bridge_image_length(&binary)? as u32;
+    let reader = CancellableReader {
+        inner: file,
+        cancelled: cancelled.clone(),
+    };
 
     // open the device
-    let (device, handle) = open_device(&context, USB_BRIDGE_VENDOR_ID, USB_BRIDGE_PRODUCT_DFU_ID)?;
+    let mut dfu_device = context.open(serial.as_deref())?;
 
-    // build the DFU interface
-    let mut dfu_iface = DfuLibusb::from_usb_device(device, handle, 0, 0)
-        .map_err(|e| Error::Install(e.to_string()))?;
-
-    // setup our progress bar
-    dfu_iface
-        .with_progress(progress_handler)
-        .override_address(DFUSE_DEFAULT_ADDRESS);
+    let mut result = VerifyResult {
+        bytes_written: length as u64,
+        verified: false,
+        mismatched_addresses: Vec::new(),
+    };
 
     // PERFORM THE INSTALL
-    match dfu_iface.download_all(file) {
+    match dfu_device.download(
+        Box::new(reader),
+        length,
+        address,
+        Box::new(progress_handler),
+    ) {
         Ok(_) => {
-            if dfu_iface.will_detach() {
-                match dfu_iface.detach() {
-                    Ok(_) => match dfu_iface.usb_reset() {
-                        Ok(_) => Ok(()),
+            if verify {
+                // release the interface so we can read the flash back, then pick it up again to detach
+                drop(dfu_device);
+                result.mismatched_addresses = verify_firmware(
+                    context,
+                    serial.as_deref(),
+                    &binary,
+                    address,
+                    length as usize,
+                    &mut verify_handler,
+                )?;
+                result.verified = result.mismatched_addresses.is_empty();
+                dfu_device = context.open(serial.as_deref())?;
+            }
+
+            if dfu_device.will_detach() {
+                match dfu_device.detach() {
+                    Ok(_) => match dfu_device.usb_reset() {
+                        Ok(_) => Ok(result),
                         Err(err) => {
                             error!("usb reset error: {}", err);
                             Err(Error::Install(err.to_string()))
@@ -89,10 +602,20 @@ where
                     }
                 }
             } else {
-                Ok(())
+                Ok(result)
             }
         }
-        Err(dfu_libusb::Error::LibUsb(rusb::Error::Io)) => Ok(()),
+        Err(_) if cancelled.load(Ordering::SeqCst) => {
+            info!("dfu download cancelled");
+            Err(Error::Cancelled)
+        }
+        Err(dfu_libusb::Error::LibUsb(rusb::Error::Io)) => {
+            // the device has already reset itself, so there's nothing left to read back
+            if verify {
+                info!("device reset after download - skipping verification");
+            }
+            Ok(result)
+        }
         Err(err) => {
             error!("dfu download error: {}", err);
             Err(Error::Install(err.to_string()))
@@ -100,14 +623,552 @@ where
     }
 }
 
+/// read back the flash we just wrote and compare it against the source file, returning any mismatched blocks
+fn verify_firmware(
+    context: &impl DfuContext,
+    serial: Option<&str>,
+    binary: &PathBuf,
+    address: u32,
+    length: usize,
+    progress: &mut dyn FnMut(usize),
+) -> Result<Vec<u32>> {
+    info!("verifying {length} bytes at {address:#010x}");
+    let (readback, transfer_size) = context.read_flash(serial, address, length, progress)?;
+
+    // only compare what was written - .dfu suffixes never make it to the device
+    let image = read(binary)?;
+    let mismatched = mismatched_blocks(
+        &image[..length.min(image.len())],
+        &readback,
+        address,
+        transfer_size as usize,
+    );
+    if mismatched.is_empty() {
+        info!("firmware verified successfully");
+    } else {
+        error!(
+            "readback does not match the firmware file in {} block(s), starting at {:#010x}",
+            mismatched.len(),
+            mismatched[0]
+        );
+    }
+    Ok(mismatched)
+}
+
+/// compare an image against what was read back a block at a time, returning the address of every block that differs.
+/// anything missing from the readback counts as a mismatch.
+fn mismatched_blocks(expected: &[u8], actual: &[u8], address: u32, block_size: usize) -> Vec<u32> {
+    expected
+        .chunks(block_size)
+        .enumerate()
+        .filter(|(index, block)| {
+            let start = index * block_size;
+            actual.get(start..start + block.len()) != Some(*block)
+        })
+        .map(|(index, _)| address + (index * block_size) as u32)
+        .collect()
+}
+
+/// read `length` bytes of flash at `address` from a device in DFU mode
+pub fn read_firmware_bytes(vid: u16, pid: u16, address: u32, length: u32) -> Result<Vec<u8>> {
+    let context = rusb::Context::new()
+        .map_err(|e| Error::USB(format!("unable to create usb context: {}", e)))?;
+    let (device, mut handle) = open_device(&context, vid, pid)?;
+    upload(&device, &mut handle, address, length as usize, &mut |_| ())
+}
+
+/// erase the entire flash of a bridge in DFU mode - it won't boot again until firmware is installed
+pub fn mass_erase(serial: Option<&str>) -> Result<()> {
+    let context = rusb::Context::new()
+        .map_err(|e| Error::USB(format!("unable to create usb context: {}", e)))?;
+    let (_, mut handle) = open_device_matching(
+        &context,
+        USB_BRIDGE_VENDOR_ID,
+        USB_BRIDGE_PRODUCT_DFU_ID,
+        serial,
+    )?;
+
+    handle
+        .claim_interface(0)
+        .map_err(|e| UsbConnectionError::from_libusb(e, USB_BRIDGE_VENDOR_ID))?;
+    handle
+        .set_alternate_setting(0, 0)
+        .map_err(|e| Error::USB(format!("unable to set dfu alt setting: {}", e)))?;
+
+    let result = dfuse_mass_erase(&handle);
+    let _ = handle.release_interface(0);
+    result
+}
+
+fn dfuse_mass_erase<C: rusb::UsbContext>(handle: &rusb::DeviceHandle<C>) -> Result<()> {
+    // make sure we're starting from dfuIDLE
+    dfu_abort(handle)?;
+
+    // an erase command without an address erases every sector
+    info!("mass erasing flash");
+    handle
+        .write_control(
+            DFU_REQUEST_OUT,
+            DFU_DNLOAD,
+            0,
+            0,
+            &[DFUSE_ERASE],
+            USB_TIMEOUT,
+        )
+        .map_err(|e| Error::USB(format!("unable to start mass erase: {}", e)))?;
+
+    // the device stays busy until the erase is done - each status waits out the poll timeout it reports
+    let deadline = Instant::now() + DFU_ERASE_TIMEOUT;
+    loop {
+        if Instant::now() > deadline {
+            err!(Error::USB(format!(
+                "dfu mass erase did not finish within {} seconds",
+                DFU_ERASE_TIMEOUT.as_secs()
+            )))
+        }
+        let (status, state) = dfu_get_status(handle)?;
+        if status != 0 || state == DFU_STATE_ERROR {
+            err!(Error::USB(format!(
+                "dfu mass erase failed - status: {status}, state: {state}"
+            )))
+        }
+        if state != DFU_STATE_DNBUSY {
+            break;
+        }
+    }
+    dfu_abort(handle)?;
+
+    info!("mass erase complete");
+    Ok(())
+}
+
+/// the transfer size the device advertises, if it tells us
+fn transfer_size<C: rusb::UsbContext>(device: &rusb::Device<C>) -> u16 {
+    match dfu_functional_descriptor(device) {
+        Some(info) => info.transfer_size,
+        None => DFUSE_TRANSFER_SIZE,
+    }
+}
+
+/// claim the dfu interface and read back a region of flash
+fn upload<C: rusb::UsbContext>(
+    device: &rusb::Device<C>,
+    handle: &mut rusb::DeviceHandle<C>,
+    address: u32,
+    length: usize,
+    progress: &mut dyn FnMut(usize),
+) -> Result<Vec<u8>> {
+    let transfer_size = transfer_size(device);
+
+    handle
+        .claim_interface(0)
+        .map_err(|e| Error::USB(format!("unable to claim dfu interface: {}", e)))?;
+    handle
+        .set_alternate_setting(0, 0)
+        .map_err(|e| Error::USB(format!("unable to set dfu alt setting: {}", e)))?;
+
+    let data = read_memory(handle, address, length, transfer_size, progress);
+    let _ = handle.release_interface(0);
+    data
+}
+
+fn dfu_get_status<C: rusb::UsbContext>(handle: &rusb::DeviceHandle<C>) -> Result<(u8, u8)> {
+    // [bStatus, bwPollTimeout (3 bytes), bState, iString]
+    let mut status = [0u8; 6];
+    handle
+        .read_control(
+            DFU_REQUEST_IN,
+            DFU_GETSTATUS,
+            0,
+            0,
+            &mut status,
+            USB_TIMEOUT,
+        )
+        .map_err(|e| Error::USB(format!("dfu get status failed: {}", e)))?;
+
+    let poll_timeout = u32::from_le_bytes([status[1], status[2], status[3], 0]);
+    std::thread::sleep(Duration::from_millis(poll_timeout as u64));
+    Ok((status[0], status[4]))
+}
+
+fn dfu_abort<C: rusb::UsbContext>(handle: &rusb::DeviceHandle<C>) -> Result<()> {
+    handle
+        .write_control(DFU_REQUEST_OUT, DFU_ABORT, 0, 0, &[], USB_TIMEOUT)
+        .map_err(|e| Error::USB(format!("dfu abort failed: {}", e)))?;
+    Ok(())
+}
+
+/// make sure a read stays inside the bridge's flash, and fits in the block numbers a DfuSe upload can address
+fn check_read_range(address: u32, length: usize, transfer_size: u16) -> Result<()> {
+    let end = (address as u64).saturating_add(length as u64);
+    if address < DFUSE_DEFAULT_ADDRESS || end > DFUSE_FLASH_END as u64 + 1 {
+        err!(Error::Other(format!(
+            "reads must be within flash ({DFUSE_DEFAULT_ADDRESS:#010x}-{DFUSE_FLASH_END:#010x}): {length} bytes at {address:#010x}"
+        )))
+    }
+
+    let transfer_size = transfer_size.max(1) as usize;
+    let blocks = length.div_ceil(transfer_size);
+    if blocks > (u16::MAX - DFUSE_FIRST_UPLOAD_BLOCK) as usize + 1 {
+        err!(Error::Other(format!(
+            "{length} bytes is too much to read {transfer_size} bytes at a time"
+        )))
+    }
+    Ok(())
+}
+
+/// read `length` bytes of flash starting at `address` using DfuSe uploads, passing the size of each block read to `progress`
+pub fn read_memory<C: rusb::UsbContext>(
+    handle: &rusb::DeviceHandle<C>,
+    address: u32,
+    length: usize,
+    transfer_size: u16,
+    progress: &mut dyn FnMut(usize),
+) -> Result<Vec<u8>> {
+    check_read_range(address, length, transfer_size)?;
+
+    // make sure we're starting from dfuIDLE
+    dfu_abort(handle)?;
+
+    // point the dfuse address pointer at where we want to read from
+    let mut command = vec![DFUSE_SET_ADDRESS];
+    command.extend_from_slice(&address.to_le_bytes());
+    handle
+        .write_control(DFU_REQUEST_OUT, DFU_DNLOAD, 0, 0, &command, USB_TIMEOUT)
+        .map_err(|e| Error::USB(format!("unable to set dfu address: {}", e)))?;
+
+    // the first status kicks off the command, the second one reports how it went
+    dfu_get_status(handle)?;
+    let (status, state) = dfu_get_status(handle)?;
+    if status != 0 {
+        err!(Error::USB(format!(
+            "dfu set address failed - status: {status}, state: {state}"
+        )))
+    }
+    dfu_abort(handle)?;
+
+    // dfuse uploads start at block 2 - block N is read from address + (N - 2) * transfer size
+    let mut data = Vec::with_capacity(length);
+    for block in DFUSE_FIRST_UPLOAD_BLOCK..=u16::MAX {
+        if data.len() >= length {
+            break;
+        }
+        let mut buffer = vec![0u8; transfer_size as usize];
+        let read = handle
+            .read_control(
+                DFU_REQUEST_IN,
+                DFU_UPLOAD,
+                block,
+                0,
+                &mut buffer,
+                USB_TIMEOUT,
+            )
+            .map_err(|e| Error::USB(format!("dfu upload failed: {}", e)))?;
+        if read == 0 {
+            break;
+        }
+        data.extend_from_slice(&buffer[..read]);
+        progress(read);
+    }
+    dfu_abort(handle)?;
+
+    data.truncate(length);
+    debug!("read {} bytes from {address:#010x}", data.len());
+    Ok(data)
+}
+
+/// attributes from a device's DFU functional descriptor
+#[derive(TS, Serialize, Debug, Clone, PartialEq)]
+#[ts(export)]
+pub struct DfuDeviceInfo {
+    pub transfer_size: u16,
+    pub dfu_version: String,
+    pub can_download: bool,
+    pub can_upload: bool,
+    pub manifestation_tolerant: bool,
+}
+
+/// find and parse the DFU functional descriptor, which lives in the extra bytes of the DFU interface
+fn dfu_functional_descriptor<C: rusb::UsbContext>(
+    device: &rusb::Device<C>,
+) -> Option<DfuDeviceInfo> {
+    let config = device.active_config_descriptor().ok()?;
+    for interface in config.interfaces() {
+        for descriptor in interface.descriptors() {
+            // DFU interfaces are application specific (0xFE) with a subclass of 0x01
+            if descriptor.class_code() != DFU_INTERFACE_CLASS
+                || descriptor.sub_class_code() != DFU_INTERFACE_SUBCLASS
+            {
+                continue;
+            }
+
+            // walk the class specific descriptors: [bLength, bDescriptorType, ...]
+            let mut extra = descriptor.extra();
+            while extra.len() >= 2 {
+                let length = extra[0] as usize;
+                if length < 2 || length > extra.len() {
+                    break;
+                }
+                if extra[1] == DFU_FUNCTIONAL_DESCRIPTOR && length >= 9 {
+                    // [bLength, bDescriptorType, bmAttributes, wDetachTimeOut, wTransferSize, bcdDFUVersion]
+                    let attributes = extra[2];
+                    let version = u16::from_le_bytes([extra[7], extra[8]]);
+                    return Some(DfuDeviceInfo {
+                        transfer_size: u16::from_le_bytes([extra[5], extra[6]]),
+                        dfu_version: format!("{:x}.{:02x}", version >> 8, version & 0xFF),
+                        can_download: attributes & 0b0001 != 0,
+                        can_upload: attributes & 0b0010 != 0,
+                        manifestation_tolerant: attributes & 0b0100 != 0,
+                    });
+                }
+                extra = &extra[length..];
+            }
+        }
+    }
+    None
+}
+
+/// any usb device exposing a DFU interface, recognised or not
+#[derive(TS, Serialize, Debug, Clone, PartialEq)]
+#[ts(export)]
+pub struct DfuDeviceDescriptor {
+    pub vid: u16,
+    pub pid: u16,
+    pub product: String,
+    pub serial: String,
+    pub interface: u8,
+    /// false when the device only has a runtime DFU interface, and needs a detach first
+    pub in_dfu_mode: bool,
+    /// true when the device draws its power from the usb bus rather than its own supply
+    pub bus_powered: bool,
+    /// the most current the device asks the bus for
+    pub max_power_ma: u16,
+}
+
+impl DfuDeviceDescriptor {
+    /// a bus powered device asking for this little often fails to flash on an underpowered hub
+    pub fn is_underpowered(&self) -> bool {
+        self.bus_powered && self.max_power_ma < LOW_BUS_POWER_MA
+    }
+}
+
+/// whether a device is bus powered, and the current (in mA) it draws - from its configuration descriptor
+pub fn read_power_info(config: &rusb::ConfigDescriptor) -> (bool, u16) {
+    (!config.self_powered(), config.max_power())
+}
+
+/// list every device with a DFU interface, regardless of vendor - handy for development builds with unknown pids
+pub fn enumerate_dfu_devices() -> Result<Vec<DfuDeviceDescriptor>> {
+    let context = rusb::Context::new()
+        .map_err(|e| Error::USB(format!("unable to create usb context: {}", e)))?;
+    let devices = context
+        .devices()
+        .map_err(|e| Error::USB(format!("unable to enumerate usb devices: {}", e)))?;
+
+    let mut found = Vec::new();
+    for device in devices.iter() {
+        let (device_desc, config) = match (
+            device.device_descriptor(),
+            device.active_config_descriptor(),
+        ) {
+            (Ok(device_desc), Ok(config)) => (device_desc, config),
+            _ => continue,
+        };
+
+        let dfu_interface = config.interfaces().find_map(|interface| {
+            interface.descriptors().find(|descriptor| {
+                descriptor.class_code() == DFU_INTERFACE_CLASS
+                    && descriptor.sub_class_code() == DFU_INTERFACE_SUBCLASS
+            })
+        });
+        let descriptor = match dfu_interface {
+            Some(descriptor) => descriptor,
+            None => continue,
+        };
+
+        // the strings need the device opened, which can fail without permission - don't skip the device over it
+        let (product, serial) = match device.open() {
+            Ok(handle) => (
+                handle
+                    .read_product_string_ascii(&device_desc)
+                    .unwrap_or_default(),
+                handle
+                    .read_serial_number_string_ascii(&device_desc)
+                    .unwrap_or_default(),
+            ),
+            Err(err) => {
+                debug!("unable to open dfu device for its strings: {}", err);
+                (String::new(), String::new())
+            }
+        };
+
+        let (bus_powered, max_power_ma) = read_power_info(&config);
+        let found_device = DfuDeviceDescriptor {
+            vid: device_desc.vendor_id(),
+            pid: device_desc.product_id(),
+            product,
+            serial,
+            interface: descriptor.interface_number(),
+            in_dfu_mode: descriptor.protocol_code() == DFU_PROTOCOL_DFU_MODE,
+            bus_powered,
+            max_power_ma,
+        };
+        if found_device.is_underpowered() {
+            warn!(
+                "dfu device {:04x}:{:04x} is bus powered and only asks for {}mA - flashing may fail on an underpowered hub",
+                found_device.vid, found_device.pid, found_device.max_power_ma
+            );
+        }
+        found.push(found_device);
+    }
+
+    debug!("dfu devices: {:?}", found);
+    Ok(found)
+}
+
+/// where a usb device sits in the hub tree - for diagnosing bandwidth and hub power issues
+#[derive(TS, Serialize, Debug, Clone, PartialEq)]
+#[ts(export)]
+pub struct UsbNodeInfo {
+    pub vid: u16,
+    pub pid: u16,
+    pub product: Option<String>,
+    pub speed: String,
+    pub bus: u8,
+    /// the ports between the root hub and the device - empty for the root hub itself
+    pub port_chain: Vec<u8>,
+    pub is_hub: bool,
+}
+
+/// list every usb device, with its bus and port chain, sorted so devices follow the hub they're plugged into
+pub fn enumerate_usb_tree() -> Result<Vec<UsbNodeInfo>> {
+    let context = rusb::Context::new()
+        .map_err(|e| Error::USB(format!("unable to create usb context: {}", e)))?;
+    let devices = context
+        .devices()
+        .map_err(|e| Error::USB(format!("unable to enumerate usb devices: {}", e)))?;
+
+    let mut nodes: Vec<UsbNodeInfo> = devices
+        .iter()
+        .filter_map(|device| {
+            let device_desc = device.device_descriptor().ok()?;
+
+            // reading the product string can fail without permission - still list the device
+            let product = device
+                .open()
+                .and_then(|handle| handle.read_product_string_ascii(&device_desc))
+                .ok();
+
+            Some(UsbNodeInfo {
+                vid: device_desc.vendor_id(),
+                pid: device_desc.product_id(),
+                product,
+                speed: format!("{:?}", device.speed()),
+                bus: device.bus_number(),
+                port_chain: device.port_numbers().unwrap_or_default(),
+                is_hub: device_desc.class_code() == USB_HUB_CLASS,
+            })
+        })
+        .collect();
+    nodes.sort_by(|a, b| (a.bus, &a.port_chain).cmp(&(b.bus, &b.port_chain)));
+
+    debug!("usb tree: {:?}", nodes);
+    Ok(nodes)
+}
+
+/// read the DFU runtime attributes of a device
+pub fn read_dfu_device_info(vid: u16, pid: u16) -> Result<DfuDeviceInfo> {
+    let context = rusb::Context::new()
+        .map_err(|e| Error::USB(format!("unable to create usb context: {}", e)))?;
+    let (device, _) = open_device(&context, vid, pid)?;
+
+    match dfu_functional_descriptor(&device) {
+        Some(info) => {
+            debug!("dfu device info: {:?}", info);
+            Ok(info)
+        }
+        None => err!(Error::USB(
+            "device does not expose a dfu functional descriptor".to_string()
+        )),
+    }
+}
+
+/// the string descriptors a usb device reports about itself
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct UsbStrings {
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+/// read the manufacturer, product, and serial number string descriptors
+pub fn read_usb_strings(vid: u16, pid: u16) -> Result<UsbStrings> {
+    let context = rusb::Context::new()
+        .map_err(|e| Error::USB(format!("unable to create usb context: {}", e)))?;
+    let (device, handle) = open_device(&context, vid, pid)?;
+
+    let device_desc = device
+        .device_descriptor()
+        .map_err(|e| Error::USB(format!("unable to read device descriptor: {}", e)))?;
+    let strings = UsbStrings {
+        manufacturer: handle.read_manufacturer_string_ascii(&device_desc).ok(),
+        product: handle.read_product_string_ascii(&device_desc).ok(),
+        serial_number: handle.read_serial_number_string_ascii(&device_desc).ok(),
+    };
+    debug!("usb strings: {:?}", strings);
+
+    Ok(strings)
+}
+
+/// determine the device type from the usb product string descriptor
+pub fn detect_device_type(vid: u16, pid: u16) -> Result<ConnectedDeviceType> {
+    match read_usb_strings(vid, pid)?.product {
+        Some(product) => Ok(ConnectedDeviceType::from_description(product.trim())),
+        None => err!(Error::USB("unable to read product string".to_string())),
+    }
+}
+
 fn open_device<C: rusb::UsbContext>(
     context: &C,
     vid: u16,
     pid: u16,
+) -> Result<(rusb::Device<C>, rusb::DeviceHandle<C>)> {
+    open_device_matching(context, vid, pid, None)
+}
+
+/// open a device, trying again after `delay` if it fails - windows doesn't always let us open a device straight after it enumerates.
+/// only the last error is returned.
+fn open_with_retry<C: rusb::UsbContext>(
+    device: &rusb::Device<C>,
+    max_attempts: u8,
+    delay: Duration,
+) -> rusb::Result<rusb::DeviceHandle<C>> {
+    let mut attempt = 1;
+    loop {
+        match device.open() {
+            Ok(handle) => return Ok(handle),
+            Err(err) if attempt < max_attempts => {
+                debug!("unable to open usb device (attempt {attempt} of {max_attempts}): {err}");
+                attempt += 1;
+                std::thread::sleep(delay);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// open the first device with a matching vid/pid - and serial number, if one is given
+fn open_device_matching<C: rusb::UsbContext>(
+    context: &C,
+    vid: u16,
+    pid: u16,
+    serial: Option<&str>,
 ) -> Result<(rusb::Device<C>, rusb::DeviceHandle<C>)> {
     let devices = context
         .devices()
         .map_err(|e| Error::USB(format!("unable to enumerate usb devices: {}", e)))?;
+    // a device we can't open might not be the one we're after, so keep looking
+    let mut last_error = None;
     for device in devices.iter() {
         let device_desc = match device.device_descriptor() {
             Ok(x) => x,
@@ -115,12 +1176,375 @@ fn open_device<C: rusb::UsbContext>(
         };
 
         if device_desc.vendor_id() == vid && device_desc.product_id() == pid {
-            let handle = device
-                .open()
-                .map_err(|e| Error::USB(format!("unable to open usb device: {}", e)))?;
+            let handle = match open_with_retry(&device, USB_OPEN_ATTEMPTS, USB_OPEN_RETRY_DELAY) {
+                Ok(handle) => handle,
+                Err(err) => {
+                    warn!("unable to open usb device {vid:04x}:{pid:04x}: {err}");
+                    last_error = Some(err);
+                    continue;
+                }
+            };
+            if let Some(serial) = serial {
+                match handle.read_serial_number_string_ascii(&device_desc) {
+                    Ok(found) if found == serial => (),
+                    _ => continue,
+                }
+            }
             return Ok((device, handle));
         }
     }
 
-    Err(Error::USB(format!("unable to find usb device")))
+    match last_error {
+        Some(err) => Err(UsbConnectionError::from_libusb(err, vid).into()),
+        None => Err(UsbConnectionError::not_found(vid, pid).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        ffi::OsStr,
+        fs::{create_dir_all, read, remove_dir_all, write},
+        io::Read,
+        path::{Path, PathBuf},
+        rc::Rc,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    use crate::{
+        device::ConnectedDeviceType,
+        dfu::{
+            check_read_range, flash_bridge, install_uf2_to, is_uf2_disk_candidate,
+            mismatched_blocks, uf2_disk_name, DfuContext, DfuDevice,
+        },
+        error::{Error, Result, UsbConnectionError, UsbErrorKind},
+        uf2::Uf2Block,
+        DFUSE_DEFAULT_ADDRESS, DFUSE_FLASH_END, RPI_DISK_NAME, RPI_DISK_SIZE,
+        USB_BRIDGE_PRODUCT_DFU_ID, USB_BRIDGE_VENDOR_ID,
+    };
+
+    /// a bridge that only exists in memory
+    #[derive(Clone)]
+    struct MockDfuContext {
+        present: bool,
+        flash: Rc<RefCell<Vec<u8>>>,
+        download_error: Option<rusb::Error>,
+        detach_fails: bool,
+        /// flip a byte of the readback, as if the flash didn't take
+        corrupt_at: Option<usize>,
+    }
+
+    impl MockDfuContext {
+        fn new() -> Self {
+            MockDfuContext {
+                present: true,
+                flash: Rc::new(RefCell::new(Vec::new())),
+                download_error: None,
+                detach_fails: false,
+                corrupt_at: None,
+            }
+        }
+    }
+
+    struct MockDfuDevice {
+        context: MockDfuContext,
+    }
+
+    impl DfuDevice for MockDfuDevice {
+        fn download(
+            &mut self,
+            mut reader: Box<dyn Read>,
+            _length: u32,
+            _address: u32,
+            mut progress: Box<dyn FnMut(usize)>,
+        ) -> std::result::Result<(), dfu_libusb::Error> {
+            if let Some(err) = self.context.download_error {
+                return Err(dfu_libusb::Error::LibUsb(err));
+            }
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).unwrap();
+            progress(data.len());
+            *self.context.flash.borrow_mut() = data;
+            Ok(())
+        }
+
+        fn will_detach(&self) -> bool {
+            true
+        }
+
+        fn detach(&mut self) -> std::result::Result<(), dfu_libusb::Error> {
+            match self.context.detach_fails {
+                true => Err(dfu_libusb::Error::LibUsb(rusb::Error::NoDevice)),
+                false => Ok(()),
+            }
+        }
+
+        fn usb_reset(&mut self) -> std::result::Result<(), dfu_libusb::Error> {
+            Ok(())
+        }
+    }
+
+    impl DfuContext for MockDfuContext {
+        fn open(&self, _serial: Option<&str>) -> Result<Box<dyn DfuDevice>> {
+            if !self.present {
+                err!(
+                    UsbConnectionError::not_found(USB_BRIDGE_VENDOR_ID, USB_BRIDGE_PRODUCT_DFU_ID)
+                        .into()
+                )
+            }
+            Ok(Box::new(MockDfuDevice {
+                context: self.clone(),
+            }))
+        }
+
+        fn read_flash(
+            &self,
+            _serial: Option<&str>,
+            _address: u32,
+            length: usize,
+            progress: &mut dyn FnMut(usize),
+        ) -> Result<(Vec<u8>, u16)> {
+            let mut data = self.flash.borrow().clone();
+            if let Some(offset) = self.corrupt_at {
+                data[offset] ^= 0xFF;
+            }
+            data.truncate(length);
+            progress(data.len());
+            Ok((data, 4))
+        }
+    }
+
+    fn mock_flash(context: &MockDfuContext, binary: &Path, verify: bool) -> Result<Vec<u32>> {
+        flash_bridge(
+            context,
+            binary.to_path_buf(),
+            None,
+            verify,
+            DFUSE_DEFAULT_ADDRESS,
+            Arc::new(AtomicBool::new(false)),
+            |_| (),
+            |_| (),
+        )
+        .map(|result| result.mismatched_addresses)
+    }
+
+    // (name, removable, total space) as sysinfo would report them
+    const MOCK_DISKS: [(&str, bool, u64); 4] = [
+        ("Macintosh HD", false, 500 * 1024 * 1024 * 1024),
+        ("RPI-RP2", false, RPI_DISK_SIZE),
+        ("RPI-RP2", true, 2 * RPI_DISK_SIZE),
+        ("rpi_rp2", true, RPI_DISK_SIZE - 1024 * 1024),
+    ];
+
+    #[test]
+    fn flashes_mock_bridge() {
+        let (downloads, _) = mock_install_dirs("dfu-flash");
+        let binary = downloads.join("bridge6_v1.0.0.0.bin");
+        let image: Vec<u8> = (0..10).collect();
+        write(&binary, &image).unwrap();
+
+        let context = MockDfuContext::new();
+        assert!(mock_flash(&context, &binary, true).unwrap().is_empty());
+        assert_eq!(*context.flash.borrow(), image);
+
+        // a bad block is reported, but the bridge is still reset
+        let context = MockDfuContext {
+            corrupt_at: Some(5),
+            ..MockDfuContext::new()
+        };
+        assert_eq!(
+            mock_flash(&context, &binary, true).unwrap(),
+            vec![DFUSE_DEFAULT_ADDRESS + 4]
+        );
+    }
+
+    #[test]
+    fn reports_mock_bridge_errors() {
+        let (downloads, _) = mock_install_dirs("dfu-errors");
+        let binary = downloads.join("bridge6_v1.0.0.0.bin");
+        write(&binary, [0u8; 10]).unwrap();
+
+        let not_found = MockDfuContext {
+            present: false,
+            ..MockDfuContext::new()
+        };
+        assert!(matches!(
+            mock_flash(&not_found, &binary, false),
+            Err(Error::UsbConnection(UsbConnectionError {
+                kind: UsbErrorKind::NotFound,
+                ..
+            }))
+        ));
+
+        let download_fails = MockDfuContext {
+            download_error: Some(rusb::Error::Pipe),
+            ..MockDfuContext::new()
+        };
+        assert!(matches!(
+            mock_flash(&download_fails, &binary, false),
+            Err(Error::Install(_))
+        ));
+
+        // the bridge resetting itself straight after the download isn't a failure
+        let resets_early = MockDfuContext {
+            download_error: Some(rusb::Error::Io),
+            ..MockDfuContext::new()
+        };
+        assert!(mock_flash(&resets_early, &binary, true).is_ok());
+
+        let detach_fails = MockDfuContext {
+            detach_fails: true,
+            ..MockDfuContext::new()
+        };
+        assert!(matches!(
+            mock_flash(&detach_fails, &binary, false),
+            Err(Error::Install(_))
+        ));
+    }
+
+    #[test]
+    fn finds_mismatched_blocks() {
+        let image: Vec<u8> = (0..10).collect();
+        assert!(mismatched_blocks(&image, &image, 0x08000000, 4).is_empty());
+
+        // every bad block is reported, not just the first
+        let mut readback = image.clone();
+        readback[1] = 0xFF;
+        readback[9] = 0xFF;
+        assert_eq!(
+            mismatched_blocks(&image, &readback, 0x08000000, 4),
+            vec![0x08000000, 0x08000008]
+        );
+
+        // a short readback fails the blocks it didn't reach
+        assert_eq!(
+            mismatched_blocks(&image, &image[..6], 0x08000000, 4),
+            vec![0x08000004, 0x08000008]
+        );
+    }
+
+    #[test]
+    fn bounds_flash_reads() {
+        let flash_size = (DFUSE_FLASH_END - DFUSE_DEFAULT_ADDRESS + 1) as usize;
+        assert!(check_read_range(DFUSE_DEFAULT_ADDRESS, flash_size, 2048).is_ok());
+
+        // anything outside of flash is refused before it reaches the device
+        assert!(check_read_range(0, 4, 2048).is_err());
+        assert!(check_read_range(DFUSE_DEFAULT_ADDRESS, flash_size + 1, 2048).is_err());
+        assert!(check_read_range(DFUSE_FLASH_END, usize::MAX, 2048).is_err());
+
+        // and so is a read that would need more blocks than a u16 can number
+        assert!(check_read_range(DFUSE_DEFAULT_ADDRESS, flash_size, 16).is_err());
+    }
+
+    fn find_mock_disk(device_type: &ConnectedDeviceType) -> Option<usize> {
+        MOCK_DISKS
+            .iter()
+            .position(|(name, removable, total_space)| {
+                is_uf2_disk_candidate(
+                    OsStr::new(name),
+                    *removable,
+                    *total_space,
+                    uf2_disk_name(device_type),
+                )
+            })
+    }
+
+    #[test]
+    fn finds_uf2_disks() {
+        // only the removable, correctly sized disk should be picked - whatever the label casing
+        assert_eq!(find_mock_disk(&ConnectedDeviceType::Click), Some(3));
+        assert_eq!(find_mock_disk(&ConnectedDeviceType::ULoop), Some(3));
+        assert_eq!(find_mock_disk(&ConnectedDeviceType::RPBootloader), Some(3));
+    }
+
+    /// a temp directory standing in for both the download location and the mounted disk
+    fn mock_install_dirs(test: &str) -> (PathBuf, PathBuf) {
+        let root = std::env::temp_dir().join(format!("pirate-midi-{test}-{}", std::process::id()));
+        let _ = remove_dir_all(&root);
+        let (downloads, disk) = (root.join("downloads"), root.join("RPI-RP2"));
+        create_dir_all(&downloads).unwrap();
+        create_dir_all(&disk).unwrap();
+        (downloads, disk)
+    }
+
+    fn mock_uf2(total_blocks: u32) -> Vec<u8> {
+        (0..total_blocks)
+            .flat_map(|block_number| {
+                Uf2Block {
+                    flags: 0,
+                    target_address: 0x1000_0000 + block_number * 256,
+                    payload_size: 256,
+                    block_number,
+                    total_blocks,
+                    file_size: 0,
+                    data: vec![block_number as u8; 476],
+                }
+                .to_bytes()
+            })
+            .collect()
+    }
+
+    #[test]
+    #[cfg(not(feature = "simulation"))]
+    fn installs_uf2_to_mock_disk() {
+        let (downloads, disk) = mock_install_dirs("uf2-install");
+        let binary = downloads.join("click_v1.0.0.0.uf2");
+        let image = mock_uf2(3);
+        write(&binary, &image).unwrap();
+
+        let mut progress = Vec::new();
+        let written = install_uf2_to(
+            binary,
+            RPI_DISK_NAME,
+            &|| Some(disk.clone()),
+            1,
+            Arc::new(AtomicBool::new(false)),
+            |done, total| progress.push((done, total)),
+        )
+        .unwrap();
+
+        assert_eq!(written, image.len() as u64);
+        assert_eq!(read(disk.join("click_v1.0.0.0.uf2")).unwrap(), image);
+        assert_eq!(progress, vec![(1, 3), (2, 3), (3, 3)]);
+    }
+
+    #[test]
+    fn stops_uf2_install_when_cancelled() {
+        let (downloads, disk) = mock_install_dirs("uf2-cancel");
+        let binary = downloads.join("click_v1.0.0.0.uf2");
+        write(&binary, mock_uf2(3)).unwrap();
+
+        let result = install_uf2_to(
+            binary,
+            RPI_DISK_NAME,
+            &|| Some(disk.clone()),
+            1,
+            Arc::new(AtomicBool::new(true)),
+            |_, _| {},
+        );
+        assert!(matches!(result, Err(crate::error::Error::Cancelled)));
+    }
+
+    #[test]
+    #[cfg(all(feature = "integration_test", not(feature = "simulation")))]
+    fn finds_mock_disk_from_env() {
+        use crate::dfu::{install_uf2, MOCK_RPI_DISK_PATH_VAR};
+
+        let (downloads, disk) = mock_install_dirs("uf2-env");
+        let binary = downloads.join("uloop_v1.0.0.0.uf2");
+        write(&binary, mock_uf2(2)).unwrap();
+        std::env::set_var(MOCK_RPI_DISK_PATH_VAR, &disk);
+
+        install_uf2(
+            binary,
+            RPI_DISK_NAME,
+            1,
+            Arc::new(AtomicBool::new(false)),
+            |_, _| {},
+        )
+        .unwrap();
+        assert!(disk.join("uloop_v1.0.0.0.uf2").exists());
+    }
 }
@@ -0,0 +1,93 @@
+use std::{fs::read, path::Path};
+
+use log::debug;
+
+use crate::error::{Error, Result};
+
+pub const DFU_SUFFIX_LENGTH: usize = 16;
+// bcdDFU values - plain DFU 1.0/1.1, and ST's DfuSe extension
+const DFU_VERSION: u16 = 0x0100;
+const DFUSE_VERSION: u16 = 0x011A;
+pub const DFUSE_PREFIX_SIGNATURE: &[u8] = b"DfuSe";
+
+/// the 16 byte suffix appended to the end of a `.dfu` file
+#[derive(Clone, Debug)]
+pub struct DfuSuffix {
+    pub device_version: u16,
+    pub product_id: u16,
+    pub vendor_id: u16,
+    pub dfu_version: u16,
+    pub crc: u32,
+}
+
+/// the crc used by dfu suffixes - crc32 without the final xor, over everything but the crc itself
+pub fn compute_dfu_crc(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFFFFFF;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// parse and validate the suffix of an in-memory `.dfu` image.
+/// images with a DfuSe prefix are rejected - only the suffix is stripped before flashing,
+/// so their prefix, target and element headers would end up in flash.
+pub fn parse_dfu_suffix_bytes(data: &[u8]) -> Result<DfuSuffix> {
+    if data.len() < DFU_SUFFIX_LENGTH {
+        err!(Error::InvalidFirmware(
+            "file is too small to contain a dfu suffix".to_string()
+        ))
+    }
+    if data.starts_with(DFUSE_PREFIX_SIGNATURE) {
+        err!(Error::InvalidFirmware(
+            "DfuSe images with a prefix aren't supported - use a plain .dfu or .bin".to_string()
+        ))
+    }
+    let raw = &data[data.len() - DFU_SUFFIX_LENGTH..];
+    let word = |offset: usize| u16::from_le_bytes([raw[offset], raw[offset + 1]]);
+
+    // the suffix is stored back to front, so the signature reads "UFD"
+    if &raw[8..11] != b"UFD" || raw[11] as usize != DFU_SUFFIX_LENGTH {
+        err!(Error::InvalidFirmware(
+            "dfu suffix signature is missing".to_string()
+        ))
+    }
+
+    let suffix = DfuSuffix {
+        device_version: word(0),
+        product_id: word(2),
+        vendor_id: word(4),
+        dfu_version: word(6),
+        crc: u32::from_le_bytes([raw[12], raw[13], raw[14], raw[15]]),
+    };
+    debug!("dfu suffix: {:?}", suffix);
+
+    if suffix.dfu_version != DFU_VERSION && suffix.dfu_version != DFUSE_VERSION {
+        err!(Error::InvalidFirmware(format!(
+            "unsupported dfu version: {:#06x}",
+            suffix.dfu_version
+        )))
+    }
+
+    let crc = compute_dfu_crc(&data[..data.len() - 4]);
+    if crc != suffix.crc {
+        err!(Error::InvalidFirmware(format!(
+            "CRC mismatch in DFU suffix - expected: {:#010x}, got: {crc:#010x}",
+            suffix.crc
+        )))
+    }
+
+    Ok(suffix)
+}
+
+/// read and validate the suffix of a `.dfu` file on disk
+pub fn parse_dfu_suffix(path: &Path) -> Result<DfuSuffix> {
+    parse_dfu_suffix_bytes(&read(path)?)
+}
@@ -1,8 +1,8 @@
-use serde::Serialize;
+use serde::{ser::SerializeStruct, Serialize, Serializer};
 use thiserror::Error;
 
 // error object
-#[derive(Error, Debug, PartialEq, Serialize)]
+#[derive(Error, Debug)]
 pub enum Error {
     #[error("unable to retrieve file: {0:?}")]
     IO(String),
@@ -20,6 +20,245 @@ pub enum Error {
     Bootloader(String),
     #[error("incompatable version: {0:?}")]
     Incompatable(String),
+    #[error("invalid firmware file: {0:?}")]
+    InvalidFirmware(String),
+    #[error("unable to reach github: {0:?}")]
+    Offline(String),
+    #[error("Github rate limit hit!")]
+    RateLimited { reset_at: Option<u64> },
+    #[error("install cancelled")]
+    Cancelled,
+    #[error("firmware verification failed at address {address:#010x}")]
+    VerificationFailed { address: u32 },
+    #[error("checksum mismatch - expected: {expected}, got: {got}")]
+    ChecksumMismatch { expected: String, got: String },
+    #[error("usb connection failed: {0}")]
+    UsbConnection(#[from] UsbConnectionError),
+    #[error("unable to recover device: {0} - hold the bootloader button while plugging the device in, then try the install again")]
+    RecoveryFailed(String),
+    // wrapped errors - these keep the original error around for `source()`
+    #[error(transparent)]
+    File(#[from] std::io::Error),
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error(transparent)]
+    LibUsb(#[from] rusb::Error),
+    #[error(transparent)]
+    Dfu(#[from] dfu_libusb::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Database(#[from] rusqlite::Error),
+}
+
+/// stable numeric error codes for the frontend to switch on - never renumber these, only add new ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum ErrorCode {
+    NotFound = 1,
+    Http = 2,
+    Usb = 3,
+    IO = 4,
+    ChecksumMismatch = 5,
+    Serial = 6,
+    Install = 7,
+    Bootloader = 8,
+    Incompatable = 9,
+    InvalidFirmware = 10,
+    Offline = 11,
+    RateLimited = 12,
+    Cancelled = 13,
+    VerificationFailed = 14,
+    Other = 15,
+    RecoveryFailed = 16,
+    UsbPermissionDenied = 17,
+    UsbInterfaceBusy = 18,
+}
+
+impl Error {
+    /// stable numeric code for the kind of error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::File(err) if err.kind() == std::io::ErrorKind::NotFound => ErrorCode::NotFound,
+            Error::IO(_) | Error::File(_) => ErrorCode::IO,
+            Error::USB(_) | Error::LibUsb(_) => ErrorCode::Usb,
+            Error::UsbConnection(err) => match err.kind {
+                UsbErrorKind::NotFound => ErrorCode::NotFound,
+                UsbErrorKind::PermissionDenied => ErrorCode::UsbPermissionDenied,
+                UsbErrorKind::InterfaceBusy => ErrorCode::UsbInterfaceBusy,
+                UsbErrorKind::IoError => ErrorCode::Usb,
+            },
+            Error::Http(_) | Error::Request(_) => ErrorCode::Http,
+            Error::RateLimited { .. } => ErrorCode::RateLimited,
+            Error::Other(_) | Error::Json(_) | Error::Database(_) => ErrorCode::Other,
+            Error::Serial(_) => ErrorCode::Serial,
+            Error::Install(_) | Error::Dfu(_) => ErrorCode::Install,
+            Error::Bootloader(_) => ErrorCode::Bootloader,
+            Error::Incompatable(_) => ErrorCode::Incompatable,
+            Error::InvalidFirmware(_) => ErrorCode::InvalidFirmware,
+            Error::Offline(_) => ErrorCode::Offline,
+            Error::Cancelled => ErrorCode::Cancelled,
+            Error::VerificationFailed { .. } => ErrorCode::VerificationFailed,
+            Error::ChecksumMismatch { .. } => ErrorCode::ChecksumMismatch,
+            Error::RecoveryFailed(_) => ErrorCode::RecoveryFailed,
+        }
+    }
+
+    /// stable name for the kind of error, used by the frontend to match on
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::IO(_) | Error::File(_) => "IO",
+            Error::USB(_) | Error::LibUsb(_) | Error::UsbConnection(_) => "USB",
+            Error::Http(_) | Error::Request(_) | Error::RateLimited { .. } => "Http",
+            Error::Other(_) | Error::Json(_) | Error::Database(_) => "Other",
+            Error::Serial(_) => "Serial",
+            Error::Install(_) | Error::Dfu(_) => "Install",
+            Error::Bootloader(_) => "Bootloader",
+            Error::Incompatable(_) => "Incompatable",
+            Error::InvalidFirmware(_) => "InvalidFirmware",
+            Error::Offline(_) => "Offline",
+            Error::Cancelled => "Cancelled",
+            Error::VerificationFailed { .. } => "VerificationFailed",
+            Error::ChecksumMismatch { .. } => "ChecksumMismatch",
+            Error::RecoveryFailed(_) => "RecoveryFailed",
+        }
+    }
+
+    /// a plain-language explanation to show people, rather than the technical message
+    pub fn user_message(&self) -> &'static str {
+        match self {
+            Error::IO(_) => "Could not read or write a file - check the app has permission to use its folders",
+            Error::File(_) => "A file the updater needs is missing or can't be opened - check permissions",
+            Error::USB(_) => "USB communication failed - try unplugging and re-plugging your device",
+            Error::LibUsb(_) => "The USB connection to your device was interrupted - try another cable or port",
+            Error::UsbConnection(err) => match err.kind {
+                UsbErrorKind::NotFound => "Your device wasn't found - make sure it's plugged in and in update mode",
+                UsbErrorKind::PermissionDenied => "The updater isn't allowed to use your device - follow the suggested fix in the error details",
+                UsbErrorKind::InterfaceBusy => "Another app is using your device - close it and try again",
+                UsbErrorKind::IoError => "Your device couldn't be opened - try another cable or port",
+            },
+            Error::Http(_) => "GitHub returned an unexpected response - try again in a moment",
+            Error::Request(_) => "Could not connect to GitHub - check your internet connection",
+            Error::RateLimited { .. } => "GitHub is limiting requests right now - wait a few minutes and try again",
+            Error::Other(_) => "Something went wrong - try again, and contact support if it keeps happening",
+            Error::Json(_) => "Some data the updater received was malformed - try again",
+            Error::Database(_) => "The install history could not be read or saved",
+            Error::Serial(_) => "Could not talk to your device - make sure no other app is connected to it",
+            Error::Install(_) => "The firmware install did not finish - reconnect your device and try again",
+            Error::Dfu(_) => "Your device stopped responding during the install - reconnect it and try again",
+            Error::Bootloader(_) => "Your device did not enter update mode - unplug it, plug it back in and try again",
+            Error::Incompatable(_) => "This firmware is not compatible with your device",
+            Error::InvalidFirmware(_) => "This firmware file is damaged or not a firmware file",
+            Error::Offline(_) => "GitHub can't be reached - check your internet connection",
+            Error::Cancelled => "The install was cancelled",
+            Error::VerificationFailed { .. } => "The firmware on your device does not match the file - install it again before using the device",
+            Error::ChecksumMismatch { .. } => "The firmware download was corrupted - download it again",
+            Error::RecoveryFailed(_) => "Your device could not be recovered - hold the bootloader button while plugging it in, then try again",
+        }
+    }
+}
+
+// errors are sent to the frontend as { kind, code, message, user_message }
+impl Serialize for Error {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Error", 4)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("code", &(self.code() as u16))?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("user_message", self.user_message())?;
+        state.end()
+    }
+}
+
+/// why a usb device couldn't be opened
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UsbErrorKind {
+    NotFound,
+    /// on linux this is almost always a missing udev rule
+    PermissionDenied,
+    /// another process has the interface claimed
+    InterfaceBusy,
+    IoError,
+}
+
+/// a usb device we couldn't open, with what the user can do about it
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("{message} - {suggested_action}")]
+pub struct UsbConnectionError {
+    pub kind: UsbErrorKind,
+    pub message: String,
+    pub suggested_action: String,
+}
+
+impl UsbConnectionError {
+    pub fn not_found(vid: u16, pid: u16) -> Self {
+        UsbConnectionError {
+            kind: UsbErrorKind::NotFound,
+            message: format!("no usb device found with id {vid:04x}:{pid:04x}"),
+            suggested_action: "make sure the device is plugged in and in its bootloader"
+                .to_string(),
+        }
+    }
+
+    /// diagnose a libusb error from opening (or claiming an interface on) a device with vendor id `vid`
+    pub fn from_libusb(err: rusb::Error, vid: u16) -> Self {
+        let kind = match err {
+            rusb::Error::NotFound | rusb::Error::NoDevice => UsbErrorKind::NotFound,
+            rusb::Error::Access => UsbErrorKind::PermissionDenied,
+            rusb::Error::Busy => UsbErrorKind::InterfaceBusy,
+            _ => UsbErrorKind::IoError,
+        };
+        let suggested_action = match kind {
+            UsbErrorKind::NotFound => {
+                "make sure the device is plugged in and in its bootloader".to_string()
+            }
+            UsbErrorKind::PermissionDenied if cfg!(target_os = "linux") => format!(
+                "add udev rule: SUBSYSTEM==\"usb\", ATTR{{idVendor}}==\"{vid:04x}\", MODE=\"0666\""
+            ),
+            UsbErrorKind::PermissionDenied => {
+                "check the device's usb driver is installed, and that no other app has it open"
+                    .to_string()
+            }
+            UsbErrorKind::InterfaceBusy => {
+                "close any other app using the device (another updater or a dfu tool), then try again"
+                    .to_string()
+            }
+            UsbErrorKind::IoError => "unplug the device, then try another cable or usb port".to_string(),
+        };
+        UsbConnectionError {
+            kind,
+            message: format!("unable to open usb device: {err}"),
+            suggested_action,
+        }
+    }
 }
 
 pub type Result<T> = ::std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{UsbConnectionError, UsbErrorKind};
+
+    #[test]
+    fn diagnoses_usb_errors() {
+        let busy = UsbConnectionError::from_libusb(rusb::Error::Busy, 0x0483);
+        assert_eq!(busy.kind, UsbErrorKind::InterfaceBusy);
+        assert_eq!(
+            UsbConnectionError::from_libusb(rusb::Error::NoDevice, 0x0483).kind,
+            UsbErrorKind::NotFound
+        );
+        assert_eq!(
+            UsbConnectionError::from_libusb(rusb::Error::Pipe, 0x0483).kind,
+            UsbErrorKind::IoError
+        );
+
+        let denied = UsbConnectionError::from_libusb(rusb::Error::Access, 0x0483);
+        assert_eq!(denied.kind, UsbErrorKind::PermissionDenied);
+        if cfg!(target_os = "linux") {
+            assert!(denied.suggested_action.contains("ATTR{idVendor}==\"0483\""));
+        }
+    }
+}
@@ -0,0 +1,27 @@
+// release fixtures for the tests, shaped like github's api responses
+
+use serde_json::json;
+
+use crate::github::{Asset, Release};
+
+/// a 4 byte asset, as github would list it
+pub fn mock_asset(name: &str) -> Asset {
+    serde_json::from_value(json!({
+        "url": "", "browser_download_url": format!("https://example.com/{name}"),
+        "id": 1, "node_id": "", "name": name, "state": "uploaded",
+        "content_type": "application/octet-stream", "size": 4, "download_count": 0,
+        "created_at": "2023-01-01T00:00:00Z", "updated_at": "2023-01-01T00:00:00Z",
+    }))
+    .unwrap()
+}
+
+/// a published release without any assets - tests add whatever they need
+pub fn mock_release(tag: &str, published_at: &str) -> Release {
+    serde_json::from_value(json!({
+        "url": "", "html_url": "", "assets_url": "", "upload_url": "",
+        "id": 1, "node_id": "", "tag_name": tag, "target_commitish": "main",
+        "body": format!("notes for {tag}"), "draft": false, "prerelease": false,
+        "created_at": published_at, "published_at": published_at, "assets": [],
+    }))
+    .unwrap()
+}
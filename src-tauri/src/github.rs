@@ -1,7 +1,27 @@
+use std::path::Path;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{stream::BoxStream, StreamExt, TryStreamExt};
+use log::{error, trace};
+use regex::Regex;
+use reqwest::{
+    header::{HeaderMap, ETAG},
+    StatusCode,
+};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
-use crate::{device::ConnectedDevice, validation::is_name_compatible};
+use crate::{
+    device::{ConnectedDevice, ConnectedDeviceType},
+    error::{Error, Result},
+    util::format_bytes,
+    validation::is_name_compatible,
+    DFU_BYTES_PER_SEC,
+};
+
+const MANIFEST_ASSET_NAME: &str = "firmware-manifest.json";
 
 #[derive(Serialize, Deserialize, TS, Debug, Clone)]
 #[serde(rename_all = "snake_case")]
@@ -23,8 +43,56 @@ pub struct Release {
     pub draft: bool,
     pub prerelease: bool,
     pub created_at: String,
-    pub published_at: Option<String>,
+    /// drafts haven't been published yet
+    #[ts(type = "string | null")]
+    pub published_at: Option<DateTime<Utc>>,
     pub assets: Vec<Asset>,
+    /// filled in from the release's `firmware-manifest.json` asset, when it has one
+    #[serde(default)]
+    pub manifest: Option<FirmwareManifest>,
+}
+
+/// published alongside a release to say exactly which asset is for which device
+#[derive(Serialize, Deserialize, TS, Debug, Clone)]
+#[ts(export)]
+pub struct FirmwareManifest {
+    pub targets: Vec<FirmwareTarget>,
+}
+
+#[derive(Serialize, Deserialize, TS, Debug, Clone)]
+#[ts(export)]
+pub struct FirmwareTarget {
+    /// the name of a `ConnectedDeviceType`, e.g. "Bridge6" or "bridge 6"
+    pub device_type: String,
+    pub asset_name: String,
+}
+
+impl FirmwareManifest {
+    /// every target for a device type - a bridge in its bootloader could be either model, so unless
+    /// the manifest has a target for the bootloader itself it gets the targets for both
+    pub fn targets_for(&self, device_type: &ConnectedDeviceType) -> Vec<&FirmwareTarget> {
+        // a target we don't recognise shouldn't match a device we don't recognise either
+        if *device_type == ConnectedDeviceType::Unknown {
+            return Vec::new();
+        }
+        let targets: Vec<&FirmwareTarget> = self
+            .targets
+            .iter()
+            .filter(|target| {
+                target
+                    .device_type
+                    .parse::<ConnectedDeviceType>()
+                    .map_or(false, |target_type| &target_type == device_type)
+            })
+            .collect();
+
+        if targets.is_empty() && *device_type == ConnectedDeviceType::BridgeBootloader {
+            let mut targets = self.targets_for(&ConnectedDeviceType::Bridge4);
+            targets.extend(self.targets_for(&ConnectedDeviceType::Bridge6));
+            return targets;
+        }
+        targets
+    }
 }
 
 #[derive(Serialize, Deserialize, TS, Debug, Clone)]
@@ -43,10 +111,697 @@ pub struct Asset {
     pub download_count: u64,
     pub created_at: String,
     pub updated_at: String,
+    /// SHA-256 digest published by github, formatted as `sha256:<hex>`
+    #[serde(default, alias = "digest")]
+    pub checksum: Option<String>,
+    /// lowercase hex SHA-1 listed next to this asset's name in the release notes, if there was one
+    #[serde(default)]
+    pub expected_sha1: Option<String>,
+}
+
+// the parts of a `GET /orgs/{org}/repos` entry we care about
+#[derive(Deserialize, Debug, Clone)]
+pub struct Repository {
+    pub name: String,
+    #[serde(default)]
+    pub archived: bool,
+}
+
+#[derive(Serialize, Deserialize, TS, Debug, Clone)]
+#[ts(export)]
+pub struct RateLimitStatus {
+    pub limit: u32,
+    pub remaining: u32,
+    /// unix timestamp (seconds) of when the quota resets
+    pub reset_at: u64,
+}
+
+// shape of the `GET /rate_limit` response - we only care about the core quota
+#[derive(Deserialize, Debug)]
+pub struct RateLimitResponse {
+    pub resources: RateLimitResources,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RateLimitResources {
+    pub core: RateLimitCore,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RateLimitCore {
+    pub limit: u32,
+    pub remaining: u32,
+    pub reset: u64,
+}
+
+impl From<RateLimitCore> for RateLimitStatus {
+    fn from(value: RateLimitCore) -> Self {
+        RateLimitStatus {
+            limit: value.limit,
+            remaining: value.remaining,
+            reset_at: value.reset,
+        }
+    }
+}
+
+/// the file extensions firmware for a device ships as
+fn firmware_extensions(device_type: &ConnectedDeviceType) -> &'static [&'static str] {
+    match device_type {
+        ConnectedDeviceType::Bridge4
+        | ConnectedDeviceType::Bridge6
+        | ConnectedDeviceType::BridgeBootloader => &["bin", "dfu"],
+        ConnectedDeviceType::Click
+        | ConnectedDeviceType::ULoop
+        | ConnectedDeviceType::RPBootloader => &["uf2"],
+        ConnectedDeviceType::Unknown => &[],
+    }
+}
+
+/// the device an asset is for, going by its name (e.g. `bridge6_v1.2.1.1.bin`)
+fn device_type_for_asset(name: &str) -> Option<ConnectedDeviceType> {
+    let name = name.to_lowercase();
+    let device_type = match name.split('_').next() {
+        Some("bridge4") => ConnectedDeviceType::Bridge4,
+        Some("bridge6") => ConnectedDeviceType::Bridge6,
+        Some("click") => ConnectedDeviceType::Click,
+        Some("uloop") => ConnectedDeviceType::ULoop,
+        _ => return None,
+    };
+
+    // signatures and checksums share the firmware's name
+    let extension = Path::new(&name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    firmware_extensions(&device_type)
+        .contains(&extension)
+        .then_some(device_type)
 }
 
 impl Asset {
     pub fn is_compatible(&self, device: &ConnectedDevice) -> bool {
-        is_name_compatible(&device, &self.name, false)
+        self.incompatibility(device).is_none()
+    }
+
+    /// explain why an asset was or wasn't matched to a device - only meant for debugging
+    pub fn compatibility_reason(&self, device: &ConnectedDevice) -> String {
+        match self.incompatibility(device) {
+            Some(reason) => format!(
+                "{} is not compatible with {}: {reason}",
+                self.name,
+                device.device_type.display_name()
+            ),
+            None => format!(
+                "{} is compatible with {}",
+                self.name,
+                device.device_type.display_name()
+            ),
+        }
+    }
+
+    /// why an asset can't be installed on a device, if it can't
+    fn incompatibility(&self, device: &ConnectedDevice) -> Option<String> {
+        let name = self.name.to_lowercase();
+        if device.device_type == ConnectedDeviceType::Unknown {
+            return Some("the device type is unknown".to_string());
+        }
+
+        let extensions = firmware_extensions(&device.device_type);
+        let extension = Path::new(&name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default();
+        if !extensions.contains(&extension) {
+            return Some(format!("expected a .{} file", extensions.join(" or .")));
+        }
+
+        if !is_name_compatible(device, &name, false) {
+            return match &device.device_details {
+                Some(details) if !details.hardware_version.is_empty() => Some(format!(
+                    "the name doesn't match hardware version {}",
+                    details.hardware_version
+                )),
+                _ => Some("the name doesn't match this device".to_string()),
+            };
+        }
+        None
+    }
+
+    pub fn human_readable_size(&self) -> String {
+        format_bytes(self.size)
+    }
+
+    /// roughly how long flashing this asset will take, in seconds
+    pub fn estimated_flash_time_secs(&self) -> f64 {
+        self.size as f64 / DFU_BYTES_PER_SEC
+    }
+
+    /// check the download url responds before committing to the whole download
+    pub async fn check_url_accessible(
+        &self,
+        client: &impl GithubClient,
+        headers: HeaderMap,
+    ) -> Result<bool> {
+        client
+            .is_accessible(&self.browser_download_url, headers)
+            .await
+    }
+}
+
+impl Release {
+    /// drafts are only visible to authenticated maintainers and should never reach end-users
+    pub fn is_draft(&self) -> bool {
+        self.draft
+    }
+
+    pub fn asset_count(&self) -> usize {
+        self.assets.len()
+    }
+
+    /// the names of every device type this release has firmware for, from the manifest or the asset names
+    pub fn supported_devices(&self) -> Vec<String> {
+        let mut devices: Vec<String> = match &self.manifest {
+            Some(manifest) => manifest
+                .targets
+                .iter()
+                .map(|target| target.device_type.clone())
+                .collect(),
+            None => self
+                .assets
+                .iter()
+                .filter_map(|asset| device_type_for_asset(&asset.name))
+                .map(|device_type| format!("{:?}", device_type))
+                .collect(),
+        };
+        devices.sort();
+        devices.dedup();
+        devices
+    }
+
+    /// the manifest asset, if this release published one
+    pub fn manifest_asset(&self) -> Option<&Asset> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == MANIFEST_ASSET_NAME)
+    }
+
+    /// the asset to install on a device - the manifest decides when there is one, otherwise go by file name
+    pub fn asset_for_device(&self, device: &ConnectedDevice) -> Option<&Asset> {
+        self.all_assets_for_device(device).into_iter().next()
+    }
+
+    /// every asset that could be installed on a device - more than one when we can't tell which hardware revision it is
+    pub fn all_assets_for_device(&self, device: &ConnectedDevice) -> Vec<&Asset> {
+        match &self.manifest {
+            Some(manifest) => {
+                let targets = manifest.targets_for(&device.device_type);
+                self.assets
+                    .iter()
+                    .filter(|asset| targets.iter().any(|target| target.asset_name == asset.name))
+                    .collect()
+            }
+            None => self
+                .assets
+                .iter()
+                .filter(|asset| asset.is_compatible(device))
+                .collect(),
+        }
+    }
+
+    /// when the release went out - drafts don't have a publish date, so fall back to when they were created
+    fn published(&self) -> Option<DateTime<Utc>> {
+        self.published_at.or_else(|| {
+            DateTime::parse_from_rfc3339(&self.created_at)
+                .ok()
+                .map(|created| created.with_timezone(&Utc))
+        })
+    }
+
+    /// whole days since the release went out
+    pub fn age_days(&self) -> u32 {
+        self.published().map_or(0, |published| {
+            (Utc::now() - published).num_days().max(0) as u32
+        })
+    }
+
+    /// the notes of every release after `older`, up to and including this one - newest first, each under its tag
+    pub fn changelog_since(&self, older: &Release, all_releases: &[Release]) -> String {
+        let (from, to) = match (older.published(), self.published()) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return self.body.clone().unwrap_or_default(),
+        };
+
+        let mut releases: Vec<(DateTime<Utc>, &Release)> = all_releases
+            .iter()
+            .filter_map(|release| release.published().map(|published| (published, release)))
+            .filter(|(published, _)| *published > from && *published <= to)
+            .collect();
+        releases.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+        releases
+            .iter()
+            .map(|(_, release)| {
+                format!(
+                    "## {}\n\n{}",
+                    release.tag_name,
+                    release.body.as_deref().unwrap_or_default().trim()
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n\n")
+    }
+
+    /// pick up `sha1:<hex>` digests from the release notes - a digest applies to the asset named on the same line
+    pub fn link_sha1_digests(&mut self) {
+        let pattern = Regex::new(r"(?i)sha1:([0-9a-f]{40})").unwrap();
+        let body = self.body.clone().unwrap_or_default();
+        for line in body.lines() {
+            let digest = match pattern.captures(line) {
+                Some(captures) => captures[1].to_lowercase(),
+                None => continue,
+            };
+            // compare whole words, so `x.bin.sig` on a line doesn't count as `x.bin`
+            let words: Vec<&str> = line
+                .split(|c: char| c.is_whitespace() || c == '`')
+                .map(|word| word.trim_matches(|c: char| ",;:()[]*\"'.".contains(c)))
+                .collect();
+            if let Some(asset) = self
+                .assets
+                .iter_mut()
+                .find(|asset| words.contains(&asset.name.as_str()))
+            {
+                asset.expected_sha1 = Some(digest);
+            }
+        }
+    }
+}
+
+/// the releases published within the last `days` days
+pub fn filter_releases_newer_than(releases: &[Release], days: u32) -> Vec<Release> {
+    releases
+        .iter()
+        .filter(|release| release.age_days() < days)
+        .cloned()
+        .collect()
+}
+
+/// a download in progress - the total size (when github tells us) and the body as it arrives
+pub struct ByteStream {
+    pub total_bytes: Option<u64>,
+    pub chunks: BoxStream<'static, Result<Bytes>>,
+}
+
+impl ByteStream {
+    /// wait for the whole body - only for small files
+    pub async fn into_bytes(self) -> Result<Vec<u8>> {
+        self.chunks
+            .map_ok(|chunk| chunk.to_vec())
+            .try_concat()
+            .await
+    }
+}
+
+/// a page of releases, or github telling us the copy we sent an etag for is still current
+pub enum ReleasesResponse {
+    Releases {
+        releases: Vec<Release>,
+        etag: Option<String>,
+    },
+    NotModified,
+}
+
+/// the requests we make to github, kept behind a trait so they can be swapped out in tests
+#[async_trait]
+pub trait GithubClient: Sync {
+    /// retrieve a list of releases - send `If-None-Match` to allow a `NotModified` response
+    async fn get_releases(&self, url: &str, headers: HeaderMap) -> Result<ReleasesResponse>;
+    /// start downloading a file
+    async fn fetch_bytes(&self, url: &str, headers: HeaderMap) -> Result<ByteStream>;
+    /// whether a HEAD request for a url comes back with a 2xx
+    async fn is_accessible(&self, url: &str, headers: HeaderMap) -> Result<bool>;
+}
+
+fn map_request_error(err: reqwest::Error) -> Error {
+    if err.is_connect() || err.is_timeout() {
+        Error::Offline(err.to_string())
+    } else {
+        Error::Request(err)
+    }
+}
+
+#[async_trait]
+impl GithubClient for reqwest::Client {
+    async fn get_releases(&self, url: &str, headers: HeaderMap) -> Result<ReleasesResponse> {
+        let res = self
+            .get(url)
+            .headers(headers)
+            .send()
+            .await
+            .map_err(map_request_error)?;
+        trace!("success [raw]: {:?}", res);
+
+        match res.status() {
+            StatusCode::OK => {
+                let etag = res
+                    .headers()
+                    .get(ETAG)
+                    .and_then(|value| value.to_str().ok())
+                    .map(String::from);
+                Ok(ReleasesResponse::Releases {
+                    releases: res.json::<Vec<Release>>().await?,
+                    etag,
+                })
+            }
+            StatusCode::NOT_MODIFIED => Ok(ReleasesResponse::NotModified),
+            StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS => {
+                error!("Rate limited from Github - headers: {:?}", res.headers());
+                let reset_at = res
+                    .headers()
+                    .get("x-ratelimit-reset")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse::<u64>().ok());
+                Err(Error::RateLimited { reset_at })
+            }
+            status => Err(Error::Http(format!(
+                "recieved an unsupported http status code: {status}"
+            ))),
+        }
+    }
+
+    async fn fetch_bytes(&self, url: &str, headers: HeaderMap) -> Result<ByteStream> {
+        let res = self
+            .get(url)
+            .headers(headers)
+            .send()
+            .await
+            .map_err(map_request_error)?;
+        if !res.status().is_success() {
+            err!(Error::Http(format!(
+                "recieved an unsupported http status code: {}",
+                res.status()
+            )))
+        }
+
+        Ok(ByteStream {
+            total_bytes: res.content_length(),
+            chunks: res
+                .bytes_stream()
+                .map(|chunk| chunk.map_err(Error::from))
+                .boxed(),
+        })
+    }
+    async fn is_accessible(&self, url: &str, headers: HeaderMap) -> Result<bool> {
+        let res = self
+            .head(url)
+            .headers(headers)
+            .send()
+            .await
+            .map_err(map_request_error)?;
+        trace!("head {url}: {}", res.status());
+        Ok(res.status().is_success())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::{
+        device::{ConnectedDevice, ConnectedDeviceType, DeviceDetails},
+        fixtures::{mock_asset, mock_release},
+        github::{filter_releases_newer_than, FirmwareManifest, FirmwareTarget},
+    };
+
+    fn mock_device(device_type: ConnectedDeviceType) -> ConnectedDevice {
+        ConnectedDevice {
+            id: String::from("test"),
+            releases: None,
+            vendor_id: 0,
+            product_id: 0,
+            description: None,
+            serial_number: Some(String::from("test")),
+            device_type,
+            device_details: Some(DeviceDetails {
+                hardware_version: String::from("v1.0.1"),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn matches_assets_to_devices() {
+        let cases = [
+            (ConnectedDeviceType::Bridge4, "bridge4_v1.0.1.1.bin", true),
+            (ConnectedDeviceType::Bridge4, "bridge4_v1.0.1.2.bin", false),
+            (ConnectedDeviceType::Bridge4, "bridge6_v1.0.1.1.bin", false),
+            (ConnectedDeviceType::Bridge6, "bridge6_v1.0.1.1.bin", true),
+            (ConnectedDeviceType::Bridge6, "BRIDGE6_v1.0.1.1.BIN", true),
+            (ConnectedDeviceType::Bridge6, "bridge6_v1.0.1.1.uf2", false),
+            (
+                ConnectedDeviceType::Bridge6,
+                "bridge6_v1.0.1.1.bin.sig",
+                false,
+            ),
+            (ConnectedDeviceType::Click, "click_v1.0.0.0.uf2", true),
+            (ConnectedDeviceType::Click, "CLiCK_v1.0.0.0.uf2", true),
+            (
+                ConnectedDeviceType::Click,
+                "click_diagnostics_v1.0.0.0.uf2",
+                false,
+            ),
+            (ConnectedDeviceType::Click, "click_v1.0.0.0.bin", false),
+            (ConnectedDeviceType::ULoop, "uloop_v1.0.0.0.uf2", true),
+            (ConnectedDeviceType::ULoop, "click_v1.0.0.0.uf2", false),
+            (ConnectedDeviceType::Unknown, "bridge6_v1.0.1.1.bin", false),
+            (ConnectedDeviceType::Unknown, "click_v1.0.0.0.uf2", false),
+        ];
+
+        for (device_type, name, expected) in cases {
+            let device = mock_device(device_type);
+            let asset = mock_asset(name);
+            assert_eq!(
+                asset.is_compatible(&device),
+                expected,
+                "{}",
+                asset.compatibility_reason(&device)
+            );
+        }
+    }
+
+    #[test]
+    fn explains_compatibility() {
+        let device = mock_device(ConnectedDeviceType::Click);
+        assert_eq!(
+            mock_asset("click_v1.0.0.0.bin").compatibility_reason(&device),
+            "click_v1.0.0.0.bin is not compatible with CLiCK: expected a .uf2 file"
+        );
+        assert_eq!(
+            mock_asset("click_v1.0.0.0.uf2").compatibility_reason(&device),
+            "click_v1.0.0.0.uf2 is compatible with CLiCK"
+        );
+    }
+
+    #[test]
+    fn filters_recent_releases() {
+        let days_ago = |days: i64| (Utc::now() - chrono::Duration::days(days)).to_rfc3339();
+        let releases = vec![
+            mock_release("v1.2.0", &days_ago(3)),
+            mock_release("v1.1.0", &days_ago(45)),
+            mock_release("v1.0.0", &days_ago(400)),
+        ];
+        assert_eq!(releases[1].age_days(), 45);
+
+        let tags = |days: u32| -> Vec<String> {
+            filter_releases_newer_than(&releases, days)
+                .into_iter()
+                .map(|release| release.tag_name)
+                .collect()
+        };
+        assert_eq!(tags(30), vec!["v1.2.0"]);
+        assert_eq!(tags(365), vec!["v1.2.0", "v1.1.0"]);
+        assert!(tags(1).is_empty());
+    }
+
+    #[test]
+    fn collects_changelogs() {
+        let releases = vec![
+            mock_release("v1.3.0", "2023-04-01T00:00:00Z"),
+            mock_release("v1.0.0", "2023-01-01T00:00:00Z"),
+            mock_release("v1.2.0", "2023-03-01T00:00:00+01:00"),
+            mock_release("v1.1.0", "2023-02-01T00:00:00Z"),
+        ];
+
+        assert_eq!(
+            releases[2].changelog_since(&releases[1], &releases),
+            "## v1.2.0\n\nnotes for v1.2.0\n\n## v1.1.0\n\nnotes for v1.1.0"
+        );
+        assert_eq!(releases[0].changelog_since(&releases[0], &releases), "");
+    }
+
+    #[test]
+    fn prefers_manifest_targets() {
+        let device = mock_device(ConnectedDeviceType::Bridge6);
+        let mut release = mock_release("v1.3.0", "2023-04-01T00:00:00Z");
+        release.assets = vec![
+            mock_asset("bridge6_v1.3.0.1.bin"),
+            mock_asset("bridge6-app.bin"),
+            mock_asset("firmware-manifest.json"),
+        ];
+
+        // file names decide when there's no manifest
+        assert_eq!(
+            release
+                .asset_for_device(&device)
+                .map(|asset| asset.name.as_str()),
+            Some("bridge6_v1.3.0.1.bin")
+        );
+
+        release.manifest = Some(FirmwareManifest {
+            targets: vec![FirmwareTarget {
+                device_type: String::from("bridge6"),
+                asset_name: String::from("bridge6-app.bin"),
+            }],
+        });
+        assert_eq!(
+            release
+                .asset_for_device(&device)
+                .map(|asset| asset.name.as_str()),
+            Some("bridge6-app.bin")
+        );
+        assert!(release
+            .asset_for_device(&mock_device(ConnectedDeviceType::Bridge4))
+            .is_none());
+    }
+
+    #[test]
+    fn gives_bootloader_bridges_every_bridge_target() {
+        let mut release = mock_release("v1.3.0", "2023-04-01T00:00:00Z");
+        release.assets = vec![
+            mock_asset("bridge4-app.bin"),
+            mock_asset("bridge6-app.bin"),
+            mock_asset("click-app.uf2"),
+            mock_asset("firmware-manifest.json"),
+        ];
+        let target = |device_type: &str, asset_name: &str| FirmwareTarget {
+            device_type: String::from(device_type),
+            asset_name: String::from(asset_name),
+        };
+        release.manifest = Some(FirmwareManifest {
+            targets: vec![
+                target("Click", "click-app.uf2"),
+                target("Bridge6", "bridge6-app.bin"),
+                target("Bridge4", "bridge4-app.bin"),
+            ],
+        });
+
+        // we can't tell which bridge it is, so both are offered - and nothing else
+        let names: Vec<&str> = release
+            .all_assets_for_device(&mock_device(ConnectedDeviceType::BridgeBootloader))
+            .into_iter()
+            .map(|asset| asset.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["bridge4-app.bin", "bridge6-app.bin"]);
+    }
+
+    #[test]
+    fn picks_assets_for_each_device() {
+        let mut release = mock_release("v1.3.0", "2023-04-01T00:00:00Z");
+        release.assets = vec![
+            mock_asset("bridge4_v1.3.0.1.bin"),
+            mock_asset("bridge4_v1.3.0.2.bin"),
+            mock_asset("bridge6_v1.3.0.1.bin"),
+            mock_asset("click_v1.3.0.0.uf2"),
+            mock_asset("uloop_v1.3.0.0.uf2"),
+        ];
+
+        let cases = [
+            (ConnectedDeviceType::Bridge4, Some("bridge4_v1.3.0.1.bin")),
+            (ConnectedDeviceType::Bridge6, Some("bridge6_v1.3.0.1.bin")),
+            (ConnectedDeviceType::Click, Some("click_v1.3.0.0.uf2")),
+            (ConnectedDeviceType::ULoop, Some("uloop_v1.3.0.0.uf2")),
+            (
+                ConnectedDeviceType::BridgeBootloader,
+                Some("bridge4_v1.3.0.1.bin"),
+            ),
+            (ConnectedDeviceType::Unknown, None),
+        ];
+        for (device_type, expected) in cases {
+            let device = mock_device(device_type);
+            assert_eq!(
+                release
+                    .asset_for_device(&device)
+                    .map(|asset| asset.name.as_str()),
+                expected
+            );
+        }
+
+        // a bare rp2040 bootloader could be either device
+        let device = mock_device(ConnectedDeviceType::RPBootloader);
+        let names: Vec<&str> = release
+            .all_assets_for_device(&device)
+            .into_iter()
+            .map(|asset| asset.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["click_v1.3.0.0.uf2", "uloop_v1.3.0.0.uf2"]);
+    }
+
+    #[test]
+    fn lists_supported_devices() {
+        let mut release = mock_release("v1.2.1.1", "2023-01-01T00:00:00Z");
+        release.assets = vec![
+            mock_asset("bridge6_v1.2.1.1.bin"),
+            mock_asset("bridge6_v1.2.1.1.bin.sig"),
+            mock_asset("bridge4_v1.2.1.2.bin"),
+            mock_asset("bridge4_v1.2.1.1.dfu"),
+            mock_asset("click_v1.2.1.1.uf2"),
+            mock_asset("CHANGELOG.md"),
+        ];
+        assert_eq!(release.asset_count(), 6);
+        assert_eq!(
+            release.supported_devices(),
+            vec!["Bridge4", "Bridge6", "Click"]
+        );
+    }
+
+    #[test]
+    fn links_sha1_digests() {
+        let mut release = mock_release("v1.3.0", "2023-04-01T00:00:00Z");
+        release.assets = vec![
+            mock_asset("bridge6_v1.3.0.1.bin"),
+            mock_asset("bridge6_v1.3.0.2.bin"),
+        ];
+        release.body = Some(String::from(
+            "fixes\n\n- bridge6_v1.3.0.1.bin SHA1:DA39A3EE5E6B4B0D3255BFEF95601890AFD80709\n- sha1:0000",
+        ));
+
+        release.link_sha1_digests();
+        assert_eq!(
+            release.assets[0].expected_sha1.as_deref(),
+            Some("da39a3ee5e6b4b0d3255bfef95601890afd80709")
+        );
+        assert_eq!(release.assets[1].expected_sha1, None);
+    }
+
+    #[test]
+    fn links_sha1_digests_to_whole_names() {
+        let mut release = mock_release("v1.3.0", "2023-04-01T00:00:00Z");
+        release.assets = vec![
+            mock_asset("bridge6_v1.3.0.1.bin"),
+            mock_asset("bridge6_v1.3.0.1.bin.sig"),
+        ];
+        release.body = Some(String::from(
+            "- `bridge6_v1.3.0.1.bin.sig` sha1:1111111111111111111111111111111111111111\n\
+             - `bridge6_v1.3.0.1.bin` sha1:2222222222222222222222222222222222222222",
+        ));
+
+        release.link_sha1_digests();
+        assert_eq!(
+            release.assets[0].expected_sha1.as_deref(),
+            Some("2222222222222222222222222222222222222222")
+        );
+        assert_eq!(
+            release.assets[1].expected_sha1.as_deref(),
+            Some("1111111111111111111111111111111111111111")
+        );
     }
 }
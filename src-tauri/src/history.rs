@@ -0,0 +1,290 @@
+use std::{fs::create_dir_all, time::Instant};
+
+use chrono::Utc;
+use log::{error, info};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use tauri::AppHandle;
+use ts_rs::TS;
+
+use crate::{
+    device::ConnectedDevice,
+    error::{Error, Result},
+    usb::InstallStage,
+    util::app_data_file,
+    HISTORY_DB_FILE,
+};
+
+/// a single install attempt, successful or not
+#[derive(TS, Serialize, Clone, Debug)]
+#[ts(export)]
+pub struct InstallRecord {
+    pub id: i64,
+    pub device_serial: String,
+    pub device_type: String,
+    pub release_tag: Option<String>,
+    pub installed_at: String,
+    pub success: bool,
+    pub error_message: Option<String>,
+}
+
+/// how long each stage of a successful bridge install took, to tell slow erases from slow usb transfers
+#[derive(TS, Serialize, Clone, Debug)]
+#[ts(export)]
+pub struct InstallTelemetry {
+    /// each stage the install went through, in order, with how long it lasted in milliseconds
+    pub phase_timings: Vec<(InstallStage, u64)>,
+    pub total_duration_ms: u64,
+    /// throughput while writing - the other stages don't move any firmware
+    pub bytes_per_second: f64,
+}
+
+/// times each stage of an install as its progress is reported
+#[derive(Debug)]
+pub struct InstallTimer {
+    started: Instant,
+    phases: Vec<(InstallStage, Instant)>,
+}
+
+impl InstallTimer {
+    pub fn start() -> Self {
+        InstallTimer {
+            started: Instant::now(),
+            phases: Vec::new(),
+        }
+    }
+
+    /// note the install has reached `stage` - progress reported for the current stage changes nothing
+    pub fn enter(&mut self, stage: InstallStage) {
+        if self.phases.last().map(|(current, _)| current) != Some(&stage) {
+            self.phases.push((stage, Instant::now()));
+        }
+    }
+
+    /// the timings so far - the current stage ends now
+    pub fn finish(&self, bytes_written: u64) -> InstallTelemetry {
+        let now = Instant::now();
+        let phase_timings: Vec<(InstallStage, u64)> = self
+            .phases
+            .iter()
+            .enumerate()
+            .map(|(index, (stage, entered))| {
+                let ended = self
+                    .phases
+                    .get(index + 1)
+                    .map_or(now, |(_, next_entered)| *next_entered);
+                (stage.clone(), (ended - *entered).as_millis() as u64)
+            })
+            .collect();
+        let writing_ms: u64 = phase_timings
+            .iter()
+            .filter(|(stage, _)| *stage == InstallStage::Writing)
+            .map(|(_, duration_ms)| duration_ms)
+            .sum();
+        InstallTelemetry {
+            phase_timings,
+            total_duration_ms: (now - self.started).as_millis() as u64,
+            bytes_per_second: match writing_ms {
+                0 => 0.0,
+                ms => bytes_written as f64 / (ms as f64 / 1000.0),
+            },
+        }
+    }
+}
+
+/// open the history database in the app data dir, creating the table if needed
+fn open(handle: &AppHandle) -> Result<Connection> {
+    let path = app_data_file(&handle.config(), HISTORY_DB_FILE)?;
+    if let Some(dir) = path.parent() {
+        create_dir_all(dir)?;
+    }
+
+    let conn = Connection::open(path)?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS firmware_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            device_serial TEXT NOT NULL,
+            device_type TEXT NOT NULL,
+            release_tag TEXT,
+            installed_at TEXT NOT NULL,
+            success INTEGER NOT NULL,
+            error_message TEXT
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS install_telemetry (
+            install_id INTEGER PRIMARY KEY REFERENCES firmware_history(id),
+            phase_timings TEXT NOT NULL,
+            total_duration_ms INTEGER NOT NULL,
+            bytes_per_second REAL NOT NULL
+        )",
+        [],
+    )?;
+    Ok(conn)
+}
+
+/// record the outcome of an install attempt, returning its id - failures to write are only logged
+pub fn record_attempt<T>(
+    handle: &AppHandle,
+    device: &ConnectedDevice,
+    release_tag: Option<String>,
+    outcome: &Result<T>,
+) -> Option<i64> {
+    let device_serial = device.serial_number.clone().unwrap_or_default();
+    let error_message = outcome.as_ref().err().map(|err| err.to_string());
+
+    let result = open(handle).and_then(|conn| {
+        conn.execute(
+            "INSERT INTO firmware_history
+                (device_serial, device_type, release_tag, installed_at, success, error_message)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                device_serial,
+                format!("{:?}", device.device_type),
+                release_tag,
+                Utc::now().to_rfc3339(),
+                outcome.is_ok(),
+                error_message,
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    });
+
+    match result {
+        Ok(id) => {
+            info!("recorded install attempt for {device_serial}");
+            Some(id)
+        }
+        Err(err) => {
+            error!("unable to record install attempt: {:?}", err);
+            None
+        }
+    }
+}
+
+/// record a bridge install attempt, along with its timings when it succeeded
+pub fn record_bridge_attempt(
+    handle: &AppHandle,
+    device: &ConnectedDevice,
+    release_tag: Option<String>,
+    outcome: &Result<InstallTelemetry>,
+) {
+    let (id, telemetry) = match (
+        record_attempt(handle, device, release_tag, outcome),
+        outcome,
+    ) {
+        (Some(id), Ok(telemetry)) => (id, telemetry),
+        _ => return,
+    };
+    let result = open(handle).and_then(|conn| {
+        conn.execute(
+            "INSERT INTO install_telemetry
+                (install_id, phase_timings, total_duration_ms, bytes_per_second)
+                VALUES (?1, ?2, ?3, ?4)",
+            params![
+                id,
+                serde_json::to_string(&telemetry.phase_timings)?,
+                telemetry.total_duration_ms as i64,
+                telemetry.bytes_per_second,
+            ],
+        )?;
+        Ok(())
+    });
+    if let Err(err) = result {
+        error!("unable to record install telemetry: {:?}", err);
+    }
+}
+
+/// the timings recorded for a successful bridge install
+pub fn load_telemetry(handle: &AppHandle, install_id: i64) -> Result<InstallTelemetry> {
+    let row = open(handle)?
+        .query_row(
+            "SELECT phase_timings, total_duration_ms, bytes_per_second
+                FROM install_telemetry
+                WHERE install_id = ?1",
+            params![install_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, f64>(2)?,
+                ))
+            },
+        )
+        .optional()?;
+    match row {
+        Some((phase_timings, total_duration_ms, bytes_per_second)) => Ok(InstallTelemetry {
+            phase_timings: serde_json::from_str(&phase_timings)?,
+            total_duration_ms: total_duration_ms as u64,
+            bytes_per_second,
+        }),
+        None => err!(Error::Other(format!(
+            "no telemetry recorded for install {install_id}"
+        ))),
+    }
+}
+
+/// every recorded install attempt, newest first - optionally for a single device
+pub fn load(handle: &AppHandle, device_serial: Option<&str>) -> Result<Vec<InstallRecord>> {
+    let conn = open(handle)?;
+    let mut statement = conn.prepare(
+        "SELECT id, device_serial, device_type, release_tag, installed_at, success, error_message
+            FROM firmware_history
+            WHERE ?1 IS NULL OR device_serial = ?1
+            ORDER BY installed_at DESC",
+    )?;
+
+    let records = statement
+        .query_map(params![device_serial], |row| {
+            Ok(InstallRecord {
+                id: row.get(0)?,
+                device_serial: row.get(1)?,
+                device_type: row.get(2)?,
+                release_tag: row.get(3)?,
+                installed_at: row.get(4)?,
+                success: row.get(5)?,
+                error_message: row.get(6)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<InstallRecord>>>()?;
+    Ok(records)
+}
+
+pub fn clear(handle: &AppHandle) -> Result<()> {
+    let conn = open(handle)?;
+    conn.execute("DELETE FROM install_telemetry", [])?;
+    conn.execute("DELETE FROM firmware_history", [])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{history::InstallTimer, usb::InstallStage};
+
+    #[test]
+    fn times_install_stages() {
+        let mut timer = InstallTimer::start();
+        timer.enter(InstallStage::Preparing);
+        timer.enter(InstallStage::Writing);
+        timer.enter(InstallStage::Writing);
+        timer.enter(InstallStage::Resetting);
+
+        let telemetry = timer.finish(1024);
+        let stages: Vec<InstallStage> = telemetry
+            .phase_timings
+            .iter()
+            .map(|(stage, _)| stage.clone())
+            .collect();
+        assert_eq!(
+            stages,
+            vec![
+                InstallStage::Preparing,
+                InstallStage::Writing,
+                InstallStage::Resetting
+            ]
+        );
+        let phases_ms: u64 = telemetry.phase_timings.iter().map(|(_, ms)| ms).sum();
+        assert!(phases_ms <= telemetry.total_duration_ms);
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use log::info;
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{filter::LevelFilter, fmt, prelude::*, reload, Registry};
+
+use crate::{
+    error::{Error, Result},
+    LOG_FILE_LEVEL, LOG_FILE_PREFIX, LOG_FILE_RETENTION, LOG_FILE_SUFFIX,
+};
+
+/// changes how much ends up in the log file while the app is running
+pub struct LogLevelHandle(reload::Handle<LevelFilter, Registry>);
+
+impl LogLevelHandle {
+    pub fn set(&self, level: LevelFilter) -> Result<()> {
+        self.0
+            .reload(level)
+            .map_err(|err| Error::Other(format!("unable to change log level: {err}")))?;
+        info!("log file level set to {level}");
+        Ok(())
+    }
+}
+
+/// the file currently being written to - the appender rolls over to a new one at midnight (utc)
+pub fn current_log_file(dir: &Path) -> PathBuf {
+    dir.join(format!(
+        "{LOG_FILE_PREFIX}.{}.{LOG_FILE_SUFFIX}",
+        Utc::now().format("%Y-%m-%d")
+    ))
+}
+
+/// write human readable logs to stderr, and json records to a daily rotating file.
+/// the returned guard flushes the file writer, so it needs to live as long as the app does.
+pub fn setup_tracing(dir: &Path) -> (Option<WorkerGuard>, LogLevelHandle) {
+    let (file_filter, level_handle) = reload::Layer::new(LOG_FILE_LEVEL);
+
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(LOG_FILE_RETENTION)
+        .build(dir);
+
+    let (file_layer, guard) = match appender {
+        Ok(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = fmt::layer()
+                .json()
+                .with_writer(writer)
+                .with_filter(file_filter);
+            (Some(layer), Some(guard))
+        }
+        Err(err) => {
+            eprintln!("unable to create log file in {}: {err}", dir.display());
+            (None, None)
+        }
+    };
+
+    let subscriber = tracing_subscriber::registry().with(file_layer).with(
+        fmt::layer()
+            .with_writer(std::io::stderr)
+            .with_filter(LevelFilter::INFO),
+    );
+
+    // `log` records are forwarded by the logger set up in main, so don't let tracing install its own
+    if let Err(err) = tracing::subscriber::set_global_default(subscriber) {
+        eprintln!("unable to set tracing subscriber: {err}");
+    }
+    (guard, LogLevelHandle(level_handle))
+}
+
+/// the last `n` lines of a log file
+pub fn read_last_lines(path: &Path, n: usize) -> Result<Vec<String>> {
+    let contents = read_to_string(path)?;
+    let lines: Vec<String> = contents.lines().map(String::from).collect();
+    let start = lines.len().saturating_sub(n);
+    Ok(lines[start..].to_vec())
+}
@@ -7,61 +7,131 @@ macro_rules! err {
     ($kind:expr) => {
         return Err($kind)
     };
-
-    ($text:expr) => {
-        err!(ErrorKind::Other($text))
-    };
 }
 
-use log::info;
-use simplelog::{CombinedLogger, Config, SimpleLogger, WriteLogger};
-use state::InstallState;
-use std::{fs::File, path::PathBuf, time::Duration};
+use log::{error, info};
+use logging::{current_log_file, setup_tracing};
+use state::{
+    AppSettings, DfuConfig, GithubConfig, GithubState, InstallState, LogDir, NetworkConfig,
+    NetworkState, SettingsState,
+};
+use std::{path::PathBuf, time::Duration};
 use tauri::{api::path::app_log_dir, CustomMenuItem, Manager, Menu, Submenu};
 
 // modules
+mod cache;
 mod commands;
+mod deep_link;
 mod device;
 mod dfu;
+mod dfu_suffix;
 mod error;
+#[cfg(test)]
+mod fixtures;
 mod github;
+mod history;
+mod logging;
+mod notifications;
+mod profiles;
+mod rollback;
+mod selftest;
+#[cfg(feature = "simulation")]
+mod simulation;
 mod state;
+mod uf2;
 mod usb;
+mod util;
 mod validation;
+mod version;
+mod watchdog;
 
 /* GLOBAL CONSTANTS */
 // usb / device
 const USB_BRIDGE_VENDOR_ID: u16 = 0x0483;
 const USB_BRIDGE_PRODUCT_DFU_ID: u16 = 0xDF11;
+const USB_RPI_VENDOR_ID: u16 = 0x2E8A;
+const USB_RPI_PRODUCT_BOOTLOADER_ID: u16 = 0x0003;
 const USB_DEFAULT_BAUD_RATE: u32 = 9600;
 const USB_POLL_INTERVAL: u32 = 1; // in seconds
 const USB_RPI_BOOTLOADER_BAUD_RATE: u32 = 1200;
 const USB_TIMEOUT: Duration = Duration::from_secs(1);
+const USB_OPEN_ATTEMPTS: u8 = 3;
+const USB_OPEN_RETRY_DELAY: Duration = Duration::from_millis(500);
+const DOWNGRADE_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+// bus powered devices asking for less tend to fail mid-flash
+const LOW_BUS_POWER_MA: u16 = 100;
+// the stock rp2040 bootloader mounts as "RPI-RP2" - CLiCK and uLoop both keep the stock label
+const RPI_DISK_NAME: &str = "RPI-RP2";
+const CLICK_DISK_NAME: &str = RPI_DISK_NAME;
+const ULOOP_DISK_NAME: &str = RPI_DISK_NAME;
+const RPI_DISK_SIZE: u64 = 128 * 1024 * 1024; // the rp2040 bootloader always reports 128 MiB
+const RPI_DISK_SIZE_TOLERANCE: u64 = 4 * 1024 * 1024; // filesystem overhead
+const RPI_DISK_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const RPI_DISK_TIMEOUT: u64 = 15; // in seconds
 const DFUSE_DEFAULT_ADDRESS: u32 = 0x08000000;
+const DFUSE_FLASH_END: u32 = 0x081FFFFF;
+const DFUSE_TRANSFER_SIZE: u16 = 2048;
+const FACTORY_FRESH_PROBE_LENGTH: u32 = 256; // erased flash reads as 0xFF
+const DFU_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const DFU_WAIT_TIMEOUT: u64 = 15; // in seconds
+const DFU_RECOVERY_TIMEOUT: u64 = 30; // in seconds
+const DFU_ERASE_TIMEOUT: Duration = Duration::from_secs(60); // a full chip erase takes tens of seconds
+const DFU_BYTES_PER_SEC: f64 = 12_288.0; // roughly what a bridge manages over usb full speed
+const BATCH_INSTALL_PARALLELISM: usize = 4;
+const WATCHDOG_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const DFU_STALL_TIMEOUT: Duration = Duration::from_secs(30);
 // github
 const GITHUB_API_URL: &str = "https://api.github.com";
 const GITHUB_BRIDGE_REPO: &str = "Pirate-MIDI-BridgeOS";
 const GITHUB_CLICK_REPO: &str = "Pirate-MIDI-CLiCK";
 const GITHUB_ULOOP_REPO: &str = "Pirate-MIDI-uLoop";
 const GITHUB_ORG: &str = "Pirate-MIDI";
+const GITHUB_FIRMWARE_REPO_PREFIX: &str = "Pirate-MIDI-";
+const GITHUB_RELEASES_PER_PAGE: u32 = 100; // the most github will return at once
+const GITHUB_RATE_LIMIT_TTL: Duration = Duration::from_secs(60);
+const GITHUB_PING_TIMEOUT: Duration = Duration::from_secs(5);
+const GITHUB_CONFIG_FILE: &str = "github_config.json";
+// keychain
+const KEYRING_SERVICE: &str = "com.piratemidi.ahoy";
+const KEYRING_GITHUB_TOKEN: &str = "github_token";
+const KEYRING_PROXY_PASSWORD: &str = "proxy_password";
+// cache
+const FIRMWARE_CACHE_DIR: &str = "firmware_cache";
+const FIRMWARE_CACHE_INDEX: &str = "releases.json";
+// diagnostics
+const DIAGNOSTICS_LOG_LINES: usize = 50;
+// rollback
+const LAST_INSTALL_FILE: &str = "last_install.json";
+// logging
+const LOG_FILE_RETENTION: usize = 7; // in days
+const LOG_FILE_LEVEL: tracing_subscriber::filter::LevelFilter =
+    tracing_subscriber::filter::LevelFilter::DEBUG;
+const LOG_FILE_PREFIX: &str = "pirate-midi-updater";
+const LOG_FILE_SUFFIX: &str = "log";
+// history
+const HISTORY_DB_FILE: &str = "history.db";
+// network
+const NETWORK_CONFIG_FILE: &str = "network_config.json";
+// settings
+const SETTINGS_FILE: &str = "settings.json";
+const NOTIFIED_RELEASES_FILE: &str = "notified_releases.json";
+// profiles
+const PROFILES_DIR: &str = "profiles";
+// deep links
+const DEEP_LINK_SCHEME: &str = "pirate-midi";
 
 fn main() {
     let context = tauri::generate_context!();
 
-    // launch time
-    let mut launch_time = chrono::offset::Utc::now()
-        .format("%Y-%m-%d-%H:%M")
-        .to_string();
-
-    // append ".txt" to the end of the launch time for filename usage
-    launch_time.push_str(".txt");
+    // a second launch from a deep link forwards the link to the running app, then exits
+    tauri_plugin_deep_link::prepare(&context.config().tauri.bundle.identifier);
 
     // setup the log file path
     let logging_path = match app_log_dir(context.config()) {
         Some(path) => path,
         None => PathBuf::from("."),
     };
-    let log_file_path = logging_path.join(launch_time);
+    let log_dir = LogDir(logging_path.clone());
 
     // setup the sentry client
     let client = sentry_tauri::sentry::init((
@@ -72,25 +142,28 @@ fn main() {
         }
     ));
 
-    // setup the terminal logger
-    let term_logger = SimpleLogger::new(log::LevelFilter::Info, Config::default());
+    // setup the terminal + rolling json file logs
+    let (_log_guard, log_level) = setup_tracing(&logging_path);
 
-    // setup the local file logger
-    let comb_logger = match File::create(&log_file_path) {
-        Ok(writer) => {
-            let file_logger = WriteLogger::new(log::LevelFilter::Trace, Config::default(), writer);
-            CombinedLogger::new(vec![term_logger, file_logger])
-        }
-        Err(_) => CombinedLogger::new(vec![term_logger]),
-    };
+    // apply the saved log level before anything else is logged
+    let settings = AppSettings::load(context.config());
+    if let Err(err) =
+        commands::logs::parse_log_level(&settings.log_level).and_then(|level| log_level.set(level))
+    {
+        // the `log` bridge isn't installed yet, so go straight to tracing
+        tracing::error!("unable to apply the saved log level: {err}");
+    }
 
-    // tie the local logger(s) to sentry
-    let logger = sentry_log::SentryLogger::with_dest(comb_logger);
+    // tie the local logs to sentry - `log` records are forwarded on to tracing
+    let logger = sentry_log::SentryLogger::with_dest(tracing_log::LogTracer::new());
     log::set_max_level(log::LevelFilter::Trace);
     log::set_boxed_logger(Box::new(logger)).unwrap();
 
     // print where the log is going to get written
-    info!("log file location: {}", log_file_path.display());
+    info!(
+        "log file location: {}",
+        current_log_file(&logging_path).display()
+    );
 
     let _guard = sentry_tauri::minidump::init(&client);
 
@@ -127,7 +200,7 @@ fn main() {
         .menu(menu)
         .on_menu_event(move |event| match event.menu_item_id() {
             "open_log_path" => open::that_detached(&logging_path).unwrap(),
-            "open_log_file" => open::that_detached(&log_file_path).unwrap(),
+            "open_log_file" => open::that_detached(current_log_file(&logging_path)).unwrap(),
             "open_help_email" => open::that_detached("mailto:info@piratemidi.com").unwrap(),
             "open_help_learn" => open::that_detached("https://learn.piratemidi.com").unwrap(),
             "open_help_discord" => open::that_detached("https://discord.gg/x722K7ksA6").unwrap(),
@@ -137,21 +210,103 @@ fn main() {
             _ => todo!("unimplemented menu item!"),
         })
         .manage(InstallState::default())
+        .manage(log_dir)
+        .manage(log_level)
+        .manage(GithubState::new(GithubConfig::load(context.config())))
+        .manage(DfuConfig::default())
+        .manage(NetworkState::new(NetworkConfig::load(context.config())))
+        .manage(SettingsState::new(settings))
         .setup(|app| {
             // listen for the 'ready' event - but we only need to hear it one time
             let handle = app.app_handle();
             app.app_handle().once_global("ready", move |_| {
                 info!("ready event recieved");
-                usb::setup_usb_listener(handle);
+                usb::setup_usb_listener(handle.app_handle());
+
+                // on windows and linux, a link that launched the app arrives as an argument
+                if let Some(link) = std::env::args().find(|arg| arg.starts_with(DEEP_LINK_SCHEME)) {
+                    deep_link::open_deep_link(link, handle);
+                }
             });
+
+            let link_handle = app.app_handle();
+            if let Err(err) = tauri_plugin_deep_link::register(DEEP_LINK_SCHEME, move |link| {
+                deep_link::open_deep_link(link, link_handle.app_handle())
+            }) {
+                error!(
+                    "unable to register the {DEEP_LINK_SCHEME}:// scheme: {:?}",
+                    err
+                );
+            }
             Ok(())
         })
         .plugin(sentry_tauri::plugin())
         .invoke_handler(tauri::generate_handler![
             crate::commands::github::fetch_releases,
+            crate::commands::github::fetch_releases_in_range,
+            crate::commands::github::fetch_recent_releases,
+            crate::commands::github::batch_fetch_releases,
+            crate::commands::github::export_release_catalog,
+            crate::commands::github::fetch_latest_release,
+            crate::commands::github::fetch_release_notes,
+            crate::commands::github::get_release_metadata,
+            crate::commands::github::fetch_asset_metadata,
+            crate::commands::github::fetch_changelog_since,
+            crate::commands::github::check_for_updates,
+            crate::commands::github::get_github_rate_limit_status,
+            crate::commands::github::ping_github_api,
+            crate::commands::github::list_github_repos,
+            crate::commands::github::set_device_repo,
+            crate::commands::github::set_github_token,
+            crate::commands::github::set_github_base_url,
+            crate::commands::github::set_network_config,
             crate::commands::install::local_binary,
             crate::commands::install::remote_binary,
+            crate::commands::install::install_bridge_local,
+            crate::commands::install::install_bridge_with_verify,
+            crate::commands::install::install_click_local,
+            crate::commands::install::install_uloop_local,
+            crate::commands::install::wipe_device,
+            crate::commands::install::recover_bridge,
+            crate::commands::install::get_install_estimate,
+            crate::commands::install::install_from_url,
+            crate::commands::install::validate_firmware_file,
+            crate::commands::install::set_dfu_address,
             crate::commands::install::post_install,
+            crate::commands::install::cancel_install,
+            crate::commands::install::rollback_firmware,
+            crate::commands::install::batch_install,
+            crate::commands::cache::clear_firmware_cache,
+            crate::commands::cache::get_temp_dir_path,
+            crate::commands::cache::cleanup_old_firmware_files,
+            crate::commands::history::get_install_history,
+            crate::commands::history::get_install_telemetry,
+            crate::commands::history::clear_install_history,
+            crate::commands::logs::get_log_file_path,
+            crate::commands::logs::read_last_log_lines,
+            crate::commands::logs::set_log_level,
+            crate::commands::app::get_app_version,
+            crate::commands::app::get_app_settings,
+            crate::commands::app::set_app_settings,
+            crate::commands::app::reset_app_settings,
+            crate::commands::device::list_connected_devices,
+            crate::commands::device::detect_connected_device,
+            crate::commands::device::read_firmware_version,
+            crate::commands::device::export_device_info,
+            crate::commands::device::get_dfu_device_info,
+            crate::commands::device::list_dfu_devices,
+            crate::commands::device::get_usb_device_tree,
+            crate::commands::device::run_device_selftest,
+            crate::commands::device::is_factory_fresh,
+            crate::commands::device::list_midi_ports,
+            crate::commands::device::get_device_midi_port,
+            crate::commands::device::get_device_profile,
+            crate::commands::device::save_device_profile,
+            crate::commands::device::wait_for_device_bootloader,
+            crate::commands::device::enter_bootloader_mode,
+            crate::commands::device::get_available_disk_space,
+            crate::commands::device::wait_for_device_reconnect,
+            crate::commands::device::read_firmware_bytes,
         ])
         .run(context)
         .expect("error while running tauri application");
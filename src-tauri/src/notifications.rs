@@ -0,0 +1,98 @@
+use std::{
+    collections::HashMap,
+    fs::{create_dir_all, read_to_string, write},
+    path::PathBuf,
+};
+
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use tauri::{api::notification::Notification, AppHandle, Manager};
+
+use crate::{
+    device::ConnectedDevice,
+    error::{Error, Result},
+    state::SettingsState,
+    util::app_data_file,
+    NOTIFIED_RELEASES_FILE,
+};
+
+/// persisted to `<app data>/notified_releases.json` - release tags we've already told the user about, keyed by device
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct NotifiedReleases {
+    devices: HashMap<String, Vec<String>>,
+}
+
+impl NotifiedReleases {
+    fn path(handle: &AppHandle) -> Result<PathBuf> {
+        app_data_file(&handle.config(), NOTIFIED_RELEASES_FILE)
+    }
+
+    pub fn load(handle: &AppHandle) -> Result<Self> {
+        let path = Self::path(handle)?;
+        if !path.exists() {
+            return Ok(NotifiedReleases::default());
+        }
+        let contents = read_to_string(&path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, handle: &AppHandle) -> Result<()> {
+        let path = Self::path(handle)?;
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        let contents = serde_json::to_string(self)?;
+        Ok(write(path, contents)?)
+    }
+
+    /// remember a tag for a device - false if we'd already seen it
+    pub fn mark(&mut self, device_key: &str, tag: &str) -> bool {
+        let tags = self.devices.entry(device_key.to_string()).or_default();
+        if tags.iter().any(|seen| seen == tag) {
+            false
+        } else {
+            tags.push(tag.to_string());
+            true
+        }
+    }
+}
+
+/// show an os notification for a new release - only once per release, per device
+pub fn notify_update_available(
+    handle: &AppHandle,
+    device: &ConnectedDevice,
+    tag: &str,
+) -> Result<()> {
+    if handle
+        .state::<SettingsState>()
+        .settings
+        .read()
+        .unwrap()
+        .suppress_notifications
+    {
+        debug!("notifications are suppressed - not notifying about {tag}");
+        return Ok(());
+    }
+
+    let device_key = device
+        .serial_number
+        .clone()
+        .unwrap_or_else(|| device.id.clone());
+    let mut notified = NotifiedReleases::load(handle)?;
+    if !notified.mark(&device_key, tag) {
+        debug!("already notified {device_key} about {tag}");
+        return Ok(());
+    }
+
+    Notification::new(&handle.config().tauri.bundle.identifier)
+        .title("Firmware update available")
+        .body(format!(
+            "Firmware update available: {tag} for {}",
+            device.display_name()
+        ))
+        .show()
+        .map_err(|err| Error::Other(format!("unable to show notification: {err}")))?;
+    info!("notified {device_key} about {tag}");
+
+    notified.save(handle)
+}
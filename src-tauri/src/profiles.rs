@@ -0,0 +1,88 @@
+use std::{
+    fs::{create_dir_all, read_to_string, write},
+    path::PathBuf,
+};
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use ts_rs::TS;
+
+use crate::{
+    device::{ConnectedDevice, ConnectedDeviceType},
+    error::{Error, Result},
+    util::app_data_file,
+    PROFILES_DIR,
+};
+
+/// per-device preferences, persisted to `<app data>/profiles/<serial>.json`
+#[derive(TS, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[ts(export)]
+pub struct DeviceProfile {
+    pub device_serial: String,
+    pub device_type: ConnectedDeviceType,
+    pub include_pre_release: bool,
+    pub verify_after_flash: bool,
+    pub auto_check_updates: bool,
+}
+
+impl DeviceProfile {
+    pub fn new(device_serial: String, device_type: ConnectedDeviceType) -> Self {
+        DeviceProfile {
+            device_serial,
+            device_type,
+            include_pre_release: false,
+            verify_after_flash: false,
+            auto_check_updates: true,
+        }
+    }
+
+    fn path(handle: &AppHandle, serial: &str) -> Result<PathBuf> {
+        // the serial becomes a file name, so don't let it go anywhere unexpected
+        if serial.is_empty() || !serial.chars().all(char::is_alphanumeric) {
+            err!(Error::Other(format!("invalid device serial: {serial}")))
+        }
+        Ok(app_data_file(&handle.config(), PROFILES_DIR)?.join(format!("{serial}.json")))
+    }
+
+    pub fn load(handle: &AppHandle, serial: &str) -> Result<Self> {
+        let contents = read_to_string(Self::path(handle, serial)?)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, handle: &AppHandle) -> Result<()> {
+        let path = Self::path(handle, &self.device_serial)?;
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        let contents = serde_json::to_string_pretty(self)?;
+        Ok(write(path, contents)?)
+    }
+}
+
+/// give a device a default profile the first time we see it
+pub fn ensure_device_profile(handle: &AppHandle, device: &ConnectedDevice) {
+    // a device in its bootloader is the same device it was before, and already has one
+    match device.device_type {
+        ConnectedDeviceType::Bridge4
+        | ConnectedDeviceType::Bridge6
+        | ConnectedDeviceType::Click
+        | ConnectedDeviceType::ULoop => (),
+        _ => return,
+    }
+    let serial = match &device.serial_number {
+        Some(serial) => serial,
+        None => return,
+    };
+    match DeviceProfile::path(handle, serial) {
+        Ok(path) if path.exists() => (),
+        Ok(_) => {
+            let profile = DeviceProfile::new(serial.clone(), device.device_type.clone());
+            match profile.save(handle) {
+                Ok(_) => info!("created a default profile for {serial}"),
+                Err(err) => error!("unable to create device profile: {:?}", err),
+            }
+        }
+        Err(err) => error!("unable to locate device profile: {:?}", err),
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    collections::HashMap,
+    fs::{create_dir_all, read_to_string, write},
+    path::PathBuf,
+};
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::{device::ConnectedDevice, error::Result, util::app_data_file, LAST_INSTALL_FILE};
+
+/// a firmware image that was successfully installed on a device
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InstallRecord {
+    pub release_tag: Option<String>,
+    pub binary: PathBuf,
+}
+
+/// the two most recent installs for a single device
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DeviceInstalls {
+    pub current: Option<InstallRecord>,
+    pub previous: Option<InstallRecord>,
+}
+
+/// persisted to `<app data>/last_install.json`, keyed by device serial number
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct LastInstall {
+    devices: HashMap<String, DeviceInstalls>,
+}
+
+impl LastInstall {
+    fn path(handle: &AppHandle) -> Result<PathBuf> {
+        app_data_file(&handle.config(), LAST_INSTALL_FILE)
+    }
+
+    pub fn load(handle: &AppHandle) -> Result<Self> {
+        let path = Self::path(handle)?;
+        if !path.exists() {
+            return Ok(LastInstall::default());
+        }
+        let contents = read_to_string(&path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, handle: &AppHandle) -> Result<()> {
+        let path = Self::path(handle)?;
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        let contents = serde_json::to_string(self)?;
+        Ok(write(path, contents)?)
+    }
+
+    pub fn previous(&self, serial_number: &str) -> Option<&InstallRecord> {
+        self.devices
+            .get(serial_number)
+            .and_then(|installs| installs.previous.as_ref())
+    }
+
+    /// the current install becomes the previous one
+    pub fn push(&mut self, serial_number: &str, record: InstallRecord) {
+        let installs = self.devices.entry(serial_number.to_string()).or_default();
+        installs.previous = installs.current.take();
+        installs.current = Some(record);
+    }
+}
+
+/// remember a successful install so it can be rolled back later - failures are only logged
+pub fn record_install(
+    handle: &AppHandle,
+    device: &ConnectedDevice,
+    binary: &PathBuf,
+    release_tag: Option<String>,
+) {
+    let serial_number = match &device.serial_number {
+        Some(serial_number) => serial_number,
+        None => return,
+    };
+
+    let result = LastInstall::load(handle).and_then(|mut last_install| {
+        last_install.push(
+            serial_number,
+            InstallRecord {
+                release_tag,
+                binary: binary.clone(),
+            },
+        );
+        last_install.save(handle)
+    });
+
+    match result {
+        Ok(_) => info!("recorded install for {serial_number}"),
+        Err(err) => error!("unable to record install: {:?}", err),
+    }
+}
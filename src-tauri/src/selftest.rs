@@ -0,0 +1,196 @@
+use log::{info, warn};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use ts_rs::TS;
+
+use crate::{
+    device::ConnectedDeviceType,
+    dfu::{find_uf2_disk, read_dfu_device_info, read_firmware_bytes, uf2_disk_name},
+    error::{Error, Result},
+    DFUSE_DEFAULT_ADDRESS, DFUSE_FLASH_END, USB_BRIDGE_PRODUCT_DFU_ID, USB_BRIDGE_VENDOR_ID,
+};
+
+// every rp2040 bootloader disk has these
+const RPI_BOOT_FILES: [&str; 2] = ["INFO_UF2.TXT", "INDEX.HTM"];
+// the initial stack pointer, then the reset handler
+const VECTOR_TABLE_HEAD: u32 = 8;
+const ERASED_FLASH_WORD: u32 = 0xFFFFFFFF;
+
+/// one step of a self-test - also sent on its own with `selftest_progress` as the test runs
+#[derive(TS, Serialize, Clone, Debug, PartialEq)]
+#[ts(export)]
+pub struct SelfTestCheck {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+#[derive(TS, Serialize, Clone, Debug)]
+#[ts(export)]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub checks: Vec<SelfTestCheck>,
+}
+
+/// collects checks, reporting each one as it finishes
+struct SelfTest<'a> {
+    handle: &'a AppHandle,
+    checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTest<'_> {
+    /// record a check, returning whether it passed so later checks that depend on it can be skipped
+    fn check(&mut self, name: &str, passed: bool, detail: String) -> bool {
+        let check = SelfTestCheck {
+            name: name.to_string(),
+            passed,
+            detail,
+        };
+        if !passed {
+            warn!("self-test check failed: {:?}", check);
+        }
+        self.handle.emit_all("selftest_progress", &check).unwrap();
+        self.checks.push(check);
+        passed
+    }
+
+    fn report(self) -> SelfTestReport {
+        SelfTestReport {
+            passed: self.checks.iter().all(|check| check.passed),
+            checks: self.checks,
+        }
+    }
+}
+
+/// check a device in its bootloader is in a state we can flash - blocks on usb and disk access
+pub fn run_selftest(
+    device_type: &ConnectedDeviceType,
+    address: u32,
+    handle: &AppHandle,
+) -> Result<SelfTestReport> {
+    let mut test = SelfTest {
+        handle,
+        checks: Vec::new(),
+    };
+    match device_type {
+        ConnectedDeviceType::BridgeBootloader => dfu_selftest(&mut test, address),
+        ConnectedDeviceType::RPBootloader
+        | ConnectedDeviceType::Click
+        | ConnectedDeviceType::ULoop => uf2_selftest(&mut test, device_type),
+        _ => err!(Error::Incompatable(
+            "put the device in its bootloader before running a self-test".to_string()
+        )),
+    }
+    let report = test.report();
+    info!("self-test passed: {}", report.passed);
+    Ok(report)
+}
+
+fn dfu_selftest(test: &mut SelfTest, address: u32) {
+    let responds = match read_dfu_device_info(USB_BRIDGE_VENDOR_ID, USB_BRIDGE_PRODUCT_DFU_ID) {
+        Ok(info) => test.check(
+            "dfu device responds",
+            true,
+            format!(
+                "dfu {} with {} byte transfers",
+                info.dfu_version, info.transfer_size
+            ),
+        ),
+        Err(err) => test.check("dfu device responds", false, err.to_string()),
+    };
+    if !responds {
+        return;
+    }
+
+    let vector_table = match read_firmware_bytes(
+        USB_BRIDGE_VENDOR_ID,
+        USB_BRIDGE_PRODUCT_DFU_ID,
+        address,
+        VECTOR_TABLE_HEAD,
+    ) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            test.check("vector table readable", false, err.to_string());
+            return;
+        }
+    };
+    test.check(
+        "vector table readable",
+        true,
+        format!("read {} bytes at {address:#010x}", vector_table.len()),
+    );
+
+    let (passed, detail) =
+        check_reset_handler(&vector_table, DFUSE_DEFAULT_ADDRESS, DFUSE_FLASH_END);
+    test.check("reset handler", passed, detail);
+}
+
+/// the reset handler should point somewhere in flash - zero or erased flash means there's no firmware to boot
+fn check_reset_handler(vector_table: &[u8], flash_start: u32, flash_end: u32) -> (bool, String) {
+    let reset_handler = match vector_table.get(4..8) {
+        Some(word) => u32::from_le_bytes(word.try_into().unwrap()),
+        None => return (false, "vector table is too short".to_string()),
+    };
+    match reset_handler {
+        0 => (false, "reset handler is zero".to_string()),
+        ERASED_FLASH_WORD => (false, "flash is erased".to_string()),
+        // thumb addresses have their low bit set
+        handler if !(flash_start..=flash_end).contains(&(handler & !1)) => (
+            false,
+            format!("reset handler {handler:#010x} is outside flash"),
+        ),
+        handler => (true, format!("reset handler at {handler:#010x}")),
+    }
+}
+
+fn uf2_selftest(test: &mut SelfTest, device_type: &ConnectedDeviceType) {
+    let disk_name = uf2_disk_name(device_type);
+    let mount_point = match find_uf2_disk(disk_name) {
+        Some(mount_point) => mount_point,
+        None => {
+            test.check(
+                "bootloader disk mounted",
+                false,
+                format!("{disk_name} is not mounted"),
+            );
+            return;
+        }
+    };
+    test.check(
+        "bootloader disk mounted",
+        true,
+        mount_point.display().to_string(),
+    );
+
+    for file in RPI_BOOT_FILES {
+        let present = mount_point.join(file).is_file();
+        let detail = match present {
+            true => format!("found {file}"),
+            false => format!("{file} is missing"),
+        };
+        test.check(&format!("{file} present"), present, detail);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{selftest::check_reset_handler, DFUSE_DEFAULT_ADDRESS, DFUSE_FLASH_END};
+
+    fn vector_table(reset_handler: u32) -> Vec<u8> {
+        let mut bytes = 0x20020000u32.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&reset_handler.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn checks_reset_handlers() {
+        let check =
+            |bytes: &[u8]| check_reset_handler(bytes, DFUSE_DEFAULT_ADDRESS, DFUSE_FLASH_END).0;
+
+        assert!(check(&vector_table(0x080001c5)));
+        assert!(!check(&vector_table(0)));
+        assert!(!check(&vector_table(0xFFFFFFFF)));
+        assert!(!check(&vector_table(0x20000101)));
+        assert!(!check(&[0; 4]));
+    }
+}
@@ -0,0 +1,125 @@
+// dry-run installs for the `simulation` feature - these stand in for the real installers in `dfu`,
+// so the whole install flow (progress events, state transitions, history) can be exercised without a device.
+
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::{info, warn};
+
+use crate::{
+    dfu::{bridge_image_length, VerifyResult},
+    error::{Error, Result},
+    uf2::{Uf2File, UF2_BLOCK_SIZE},
+    DFUSE_TRANSFER_SIZE, DFU_BYTES_PER_SEC,
+};
+
+/// how long a real device would take to accept `bytes`
+fn transfer_time(bytes: usize) -> Duration {
+    Duration::from_secs_f64(bytes as f64 / DFU_BYTES_PER_SEC)
+}
+
+/// pretend to flash a bridge - same arguments as `dfu::install_bridge`
+pub fn install_bridge<F>(
+    binary: PathBuf,
+    _serial: Option<String>,
+    _verify: bool,
+    address: u32,
+    cancelled: Arc<AtomicBool>,
+    mut progress_handler: F,
+) -> Result<()>
+where
+    F: FnMut(usize) + 'static,
+{
+    let length = bridge_image_length(&binary)? as usize;
+    warn!(
+        "simulating a {length} byte bridge install at {address:#010x} - no device will be touched"
+    );
+
+    let mut remaining = length;
+    while remaining > 0 {
+        if cancelled.load(Ordering::SeqCst) {
+            info!("simulated dfu download cancelled");
+            err!(Error::Cancelled)
+        }
+        let chunk = remaining.min(DFUSE_TRANSFER_SIZE as usize);
+        std::thread::sleep(transfer_time(chunk));
+        remaining -= chunk;
+        progress_handler(chunk);
+    }
+    Ok(())
+}
+
+/// pretend to flash and read back a bridge - same arguments as `dfu::install_bridge_with_verify`
+pub fn install_bridge_with_verify<F, G>(
+    binary: PathBuf,
+    serial: Option<String>,
+    address: u32,
+    cancelled: Arc<AtomicBool>,
+    progress_handler: F,
+    mut verify_handler: G,
+) -> Result<VerifyResult>
+where
+    F: FnMut(usize) + 'static,
+    G: FnMut(usize),
+{
+    install_bridge(
+        binary.clone(),
+        serial,
+        true,
+        address,
+        cancelled.clone(),
+        progress_handler,
+    )?;
+
+    // a readback takes about as long as the download did
+    let length = bridge_image_length(&binary)? as usize;
+    let mut remaining = length;
+    while remaining > 0 {
+        if cancelled.load(Ordering::SeqCst) {
+            info!("simulated readback cancelled");
+            err!(Error::Cancelled)
+        }
+        let chunk = remaining.min(DFUSE_TRANSFER_SIZE as usize);
+        std::thread::sleep(transfer_time(chunk));
+        remaining -= chunk;
+        verify_handler(chunk);
+    }
+    Ok(VerifyResult {
+        bytes_written: length as u64,
+        verified: true,
+        mismatched_addresses: Vec::new(),
+    })
+}
+
+/// pretend to copy a UF2 image onto a device - same arguments as `dfu::install_uf2_to`
+pub fn install_uf2_to<F>(
+    binary: PathBuf,
+    disk_name: &str,
+    _disk_finder: &dyn Fn() -> Option<PathBuf>,
+    _timeout_secs: u64,
+    cancelled: Arc<AtomicBool>,
+    mut progress_handler: F,
+) -> Result<u64>
+where
+    F: FnMut(usize, usize),
+{
+    let image = Uf2File::from_path(&binary)?;
+    let total_blocks = image.block_count();
+    warn!("simulating a {total_blocks} block install to {disk_name} - no device will be touched");
+
+    for index in 0..total_blocks {
+        if cancelled.load(Ordering::SeqCst) {
+            info!("simulated uf2 install cancelled after {index} of {total_blocks} blocks");
+            err!(Error::Cancelled)
+        }
+        std::thread::sleep(transfer_time(UF2_BLOCK_SIZE));
+        progress_handler(index + 1, total_blocks);
+    }
+    Ok((total_blocks * UF2_BLOCK_SIZE) as u64)
+}
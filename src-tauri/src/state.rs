@@ -1,14 +1,29 @@
 use std::{
+    collections::HashMap,
+    fs::{create_dir_all, read_to_string, write},
     path::PathBuf,
-    sync::{PoisonError, RwLock, RwLockWriteGuard},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, PoisonError, RwLock, RwLockWriteGuard,
+    },
+    time::Instant,
 };
 
-use log::{debug, error};
-use serde::Serialize;
-use tauri::{AppHandle, Manager};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Config, Manager};
 use ts_rs::TS;
 
-use crate::{device::ConnectedDevice, error::Result};
+use crate::{
+    device::{ConnectedDevice, ConnectedDeviceType},
+    error::{Error, Result},
+    github::{RateLimitStatus, Release},
+    logging::current_log_file,
+    util::app_data_file,
+    DFUSE_DEFAULT_ADDRESS, GITHUB_API_URL, GITHUB_BRIDGE_REPO, GITHUB_CLICK_REPO,
+    GITHUB_CONFIG_FILE, GITHUB_ULOOP_REPO, KEYRING_GITHUB_TOKEN, KEYRING_PROXY_PASSWORD,
+    KEYRING_SERVICE, LOG_FILE_LEVEL, NETWORK_CONFIG_FILE, SETTINGS_FILE,
+};
 
 #[derive(Default, TS, Serialize, Clone, Debug)]
 #[ts(export)]
@@ -19,14 +34,352 @@ pub enum InstallerState {
     Bootloader {
         device: Box<ConnectedDevice>,
         binary: PathBuf,
+        verify: bool,
+        release_tag: Option<String>,
     },
     PostInstall,
 }
 
+/// user supplied github settings
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct GithubConfig {
+    /// kept in the os keychain - never written to the config file
+    #[serde(skip)]
+    pub token: Option<String>,
+    /// which repo hosts the firmware for each device - can be changed at runtime as new products ship
+    #[serde(skip)]
+    pub repos: HashMap<ConnectedDeviceType, String>,
+    /// the api to fetch releases from - a github enterprise instance for firmware hosted privately
+    pub base_url: String,
+}
+
+impl Default for GithubConfig {
+    fn default() -> Self {
+        let repos = [
+            (ConnectedDeviceType::Bridge4, GITHUB_BRIDGE_REPO),
+            (ConnectedDeviceType::Bridge6, GITHUB_BRIDGE_REPO),
+            // usually a bridge that's never been flashed, or is being recovered
+            (ConnectedDeviceType::BridgeBootloader, GITHUB_BRIDGE_REPO),
+            (ConnectedDeviceType::Click, GITHUB_CLICK_REPO),
+            (ConnectedDeviceType::ULoop, GITHUB_ULOOP_REPO),
+        ]
+        .into_iter()
+        .map(|(device_type, repo)| (device_type, repo.to_string()))
+        .collect();
+
+        GithubConfig {
+            token: None,
+            repos,
+            base_url: GITHUB_API_URL.to_string(),
+        }
+    }
+}
+
+impl GithubConfig {
+    /// the repo that hosts the firmware for a device type
+    pub fn repo_for(&self, device_type: &ConnectedDeviceType) -> Result<String> {
+        match self.repos.get(device_type) {
+            Some(repo) => Ok(repo.clone()),
+            None => err!(Error::Other(
+                "github releases do not exist for this device type".to_string()
+            )),
+        }
+    }
+
+    fn path(config: &Config) -> Result<PathBuf> {
+        app_data_file(config, GITHUB_CONFIG_FILE)
+    }
+
+    fn keyring_entry() -> Result<keyring::Entry> {
+        keyring::Entry::new(KEYRING_SERVICE, KEYRING_GITHUB_TOKEN)
+            .map_err(|err| Error::Other(format!("unable to access the keychain: {err}")))
+    }
+
+    /// load the saved settings, and the token from the os keychain if there is one
+    pub fn load(config: &Config) -> GithubConfig {
+        let github_config = match Self::path(config).and_then(|path| Ok(read_to_string(path)?)) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+                error!("unable to parse github config: {err}");
+                GithubConfig::default()
+            }),
+            Err(_) => GithubConfig::default(),
+        };
+
+        let token = match Self::keyring_entry().map(|entry| entry.get_password()) {
+            Ok(Ok(token)) => Some(token),
+            Ok(Err(keyring::Error::NoEntry)) => None,
+            Ok(Err(err)) => {
+                error!("unable to read github token from the keychain: {err}");
+                None
+            }
+            Err(err) => {
+                error!("{err}");
+                None
+            }
+        };
+        GithubConfig {
+            token,
+            ..github_config
+        }
+    }
+
+    /// persist the settings to the app data dir, and the token to the os keychain - removing it when unset
+    pub fn save(&self, config: &Config) -> Result<()> {
+        let path = Self::path(config)?;
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        write(&path, serde_json::to_string_pretty(self)?)?;
+
+        let entry = Self::keyring_entry()?;
+        let result = match &self.token {
+            Some(token) => entry.set_password(token),
+            None => match entry.delete_password() {
+                Err(keyring::Error::NoEntry) => Ok(()),
+                result => result,
+            },
+        };
+        result.map_err(|err| Error::Other(format!("unable to save github token: {err}")))
+    }
+}
+
+/// session wide github bookkeeping
+#[derive(Default)]
+pub struct GithubState {
+    /// the last known rate limit, and when we fetched it
+    pub rate_limit: RwLock<Option<(Instant, RateLimitStatus)>>,
+    pub config: RwLock<GithubConfig>,
+    /// firmware repos in the org, fetched once per session
+    pub org_repos: RwLock<Option<Vec<String>>>,
+    pub release_etags: ReleaseEtagCache,
+}
+
+impl GithubState {
+    pub fn new(config: GithubConfig) -> Self {
+        GithubState {
+            config: RwLock::new(config),
+            ..Default::default()
+        }
+    }
+}
+
+/// the last page of releases github sent for each request url, along with its etag -
+/// conditional requests that come back 304 don't count against the rate limit
+#[derive(Default)]
+pub struct ReleaseEtagCache {
+    entries: RwLock<HashMap<String, (String, Vec<Release>)>>,
+}
+
+impl ReleaseEtagCache {
+    pub fn etag(&self, url: &str) -> Option<String> {
+        let entries = self.entries.read().unwrap();
+        entries.get(url).map(|(etag, _)| etag.clone())
+    }
+
+    pub fn releases(&self, url: &str) -> Option<Vec<Release>> {
+        let entries = self.entries.read().unwrap();
+        entries.get(url).map(|(_, releases)| releases.clone())
+    }
+
+    pub fn insert(&self, url: &str, etag: String, releases: Vec<Release>) {
+        let mut entries = self.entries.write().unwrap();
+        entries.insert(url.to_string(), (etag, releases));
+    }
+
+    pub fn clear(&self) {
+        self.entries.write().unwrap().clear();
+    }
+}
+
+/// proxy settings for users who can't reach github directly
+#[derive(TS, Serialize, Deserialize, Default, Clone, Debug)]
+#[ts(export)]
+pub struct NetworkConfig {
+    pub proxy_url: Option<String>,
+    pub proxy_username: Option<String>,
+    /// kept in the os keychain - never written to the config file
+    pub proxy_password: Option<String>,
+    /// hosts that should bypass the proxy
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+}
+
+impl NetworkConfig {
+    fn path(config: &Config) -> Result<PathBuf> {
+        app_data_file(config, NETWORK_CONFIG_FILE)
+    }
+
+    fn keyring_entry() -> Result<keyring::Entry> {
+        keyring::Entry::new(KEYRING_SERVICE, KEYRING_PROXY_PASSWORD)
+            .map_err(|err| Error::Other(format!("unable to access the keychain: {err}")))
+    }
+
+    /// load the saved settings, falling back to no proxy at all
+    pub fn load(config: &Config) -> NetworkConfig {
+        let mut network_config = match Self::path(config).and_then(|path| Ok(read_to_string(path)?))
+        {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+                error!("unable to parse network config: {err}");
+                NetworkConfig::default()
+            }),
+            Err(_) => return NetworkConfig::default(),
+        };
+
+        network_config.proxy_password =
+            match Self::keyring_entry().map(|entry| entry.get_password()) {
+                Ok(Ok(password)) => Some(password),
+                Ok(Err(keyring::Error::NoEntry)) => None,
+                Ok(Err(err)) => {
+                    error!("unable to read proxy password from the keychain: {err}");
+                    None
+                }
+                Err(err) => {
+                    error!("{err}");
+                    None
+                }
+            };
+        network_config
+    }
+
+    /// persist the settings to the app data dir, and the password to the os keychain
+    pub fn save(&self, config: &Config) -> Result<()> {
+        let path = Self::path(config)?;
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        let on_disk = NetworkConfig {
+            proxy_password: None,
+            ..self.clone()
+        };
+        write(&path, serde_json::to_string_pretty(&on_disk)?)?;
+
+        let entry = Self::keyring_entry()?;
+        let result = match &self.proxy_password {
+            Some(password) => entry.set_password(password),
+            None => match entry.delete_password() {
+                Err(keyring::Error::NoEntry) => Ok(()),
+                result => result,
+            },
+        };
+        result.map_err(|err| Error::Other(format!("unable to save proxy password: {err}")))?;
+
+        info!("network config saved to: {}", path.display());
+        Ok(())
+    }
+}
+
+/// session wide network settings
+pub struct NetworkState {
+    pub config: RwLock<NetworkConfig>,
+}
+
+impl NetworkState {
+    pub fn new(config: NetworkConfig) -> Self {
+        NetworkState {
+            config: RwLock::new(config),
+        }
+    }
+}
+
+/// user preferences, persisted to `<app data>/settings.json`.
+/// the github token isn't one of them - it lives in the os keychain, see `set_github_token`.
+#[derive(TS, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[ts(export)]
+#[serde(default)]
+pub struct AppSettings {
+    /// don't show os notifications when a background check finds new firmware
+    pub suppress_notifications: bool,
+    /// include pre-releases when a request doesn't say either way
+    pub include_pre_release: bool,
+    /// check connected devices for updates as soon as the app opens - the frontend decides when that is
+    pub auto_check_on_launch: bool,
+    /// "system", "light" or "dark" - only the frontend uses it
+    pub theme: String,
+    /// keep downloaded firmware around for offline installs
+    pub cache_enabled: bool,
+    /// how much is written to the log file (off, error, warn, info, debug, trace)
+    pub log_level: String,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        AppSettings {
+            suppress_notifications: false,
+            include_pre_release: false,
+            auto_check_on_launch: true,
+            theme: "system".to_string(),
+            cache_enabled: true,
+            log_level: LOG_FILE_LEVEL.to_string().to_lowercase(),
+        }
+    }
+}
+
+impl AppSettings {
+    fn path(config: &Config) -> Result<PathBuf> {
+        app_data_file(config, SETTINGS_FILE)
+    }
+
+    /// load the saved settings, falling back to the defaults
+    pub fn load(config: &Config) -> AppSettings {
+        match Self::path(config).and_then(|path| Ok(read_to_string(path)?)) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+                error!("unable to parse settings: {err}");
+                AppSettings::default()
+            }),
+            Err(_) => AppSettings::default(),
+        }
+    }
+
+    pub fn save(&self, config: &Config) -> Result<()> {
+        let path = Self::path(config)?;
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        Ok(write(path, serde_json::to_string_pretty(self)?)?)
+    }
+}
+
+pub struct SettingsState {
+    pub settings: RwLock<AppSettings>,
+}
+
+impl SettingsState {
+    pub fn new(settings: AppSettings) -> Self {
+        SettingsState {
+            settings: RwLock::new(settings),
+        }
+    }
+}
+
+/// dfu settings for advanced users - the defaults are correct for every stock bridge
+pub struct DfuConfig {
+    /// base address firmware is flashed to
+    pub address: RwLock<u32>,
+}
+
+impl Default for DfuConfig {
+    fn default() -> Self {
+        DfuConfig {
+            address: RwLock::new(DFUSE_DEFAULT_ADDRESS),
+        }
+    }
+}
+
+/// directory the rolling log files are written to
+pub struct LogDir(pub PathBuf);
+
+impl LogDir {
+    pub fn current_file(&self) -> PathBuf {
+        current_log_file(&self.0)
+    }
+}
+
 #[derive(Default)]
 pub struct InstallState {
     pub devices: RwLock<Vec<ConnectedDevice>>,
     pub current_state: RwLock<InstallerState>,
+    pub cancelled: Arc<AtomicBool>,
 }
 
 impl InstallState {
@@ -44,6 +397,18 @@ impl InstallState {
             .unwrap();
     }
 
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn reset_cancel(&self) {
+        self.cancelled.store(false, Ordering::SeqCst);
+    }
+
     pub fn add_device(
         &self,
         device: ConnectedDevice,
@@ -122,8 +487,13 @@ impl InstallState {
         &self,
         device: ConnectedDevice,
         binary: PathBuf,
+        verify: bool,
+        release_tag: Option<String>,
         handle: &AppHandle,
     ) -> Result<()> {
+        // every install starts here, so clear out any previous cancellation
+        self.reset_cancel();
+
         let write = match self.current_state.write() {
             Ok(mut guard) => {
                 // enter the bootloader
@@ -133,6 +503,8 @@ impl InstallState {
                         *guard = InstallerState::Bootloader {
                             device: Box::new(device),
                             binary,
+                            verify,
+                            release_tag,
                         };
                         Ok(())
                     }
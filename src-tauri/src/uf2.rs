@@ -0,0 +1,129 @@
+use std::{fs::read, path::Path};
+
+use crate::error::{Error, Result};
+
+pub const UF2_BLOCK_SIZE: usize = 512;
+pub const UF2_MAGIC_START0: u32 = 0x0A324655;
+pub const UF2_MAGIC_START1: u32 = 0x9E5D5157;
+pub const UF2_MAGIC_END: u32 = 0x0AB16F30;
+const UF2_HEADER_SIZE: usize = 32;
+const UF2_DATA_SIZE: usize = 476;
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// a single 512 byte block of a UF2 image
+#[derive(Clone, Debug)]
+pub struct Uf2Block {
+    pub flags: u32,
+    pub target_address: u32,
+    pub payload_size: u32,
+    pub block_number: u32,
+    pub total_blocks: u32,
+    /// total file size, or the board family id when the family flag is set
+    pub file_size: u32,
+    pub data: Vec<u8>,
+}
+
+impl Uf2Block {
+    pub fn parse(raw: &[u8]) -> Result<Uf2Block> {
+        if raw.len() != UF2_BLOCK_SIZE {
+            err!(Error::InvalidFirmware(format!(
+                "uf2 blocks must be {UF2_BLOCK_SIZE} bytes"
+            )))
+        }
+        if read_u32(raw, 0) != UF2_MAGIC_START0 || read_u32(raw, 4) != UF2_MAGIC_START1 {
+            err!(Error::InvalidFirmware(
+                "uf2 block magic is missing".to_string()
+            ))
+        }
+        if read_u32(raw, UF2_BLOCK_SIZE - 4) != UF2_MAGIC_END {
+            err!(Error::InvalidFirmware("uf2 block is truncated".to_string()))
+        }
+
+        let payload_size = read_u32(raw, 16);
+        if payload_size as usize > UF2_DATA_SIZE {
+            err!(Error::InvalidFirmware(format!(
+                "uf2 payload is too large: {payload_size}"
+            )))
+        }
+
+        Ok(Uf2Block {
+            flags: read_u32(raw, 8),
+            target_address: read_u32(raw, 12),
+            payload_size,
+            block_number: read_u32(raw, 20),
+            total_blocks: read_u32(raw, 24),
+            file_size: read_u32(raw, 28),
+            data: raw[UF2_HEADER_SIZE..UF2_HEADER_SIZE + UF2_DATA_SIZE].to_vec(),
+        })
+    }
+
+    /// serialize the block back into its on-disk form
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(UF2_BLOCK_SIZE);
+        for word in [
+            UF2_MAGIC_START0,
+            UF2_MAGIC_START1,
+            self.flags,
+            self.target_address,
+            self.payload_size,
+            self.block_number,
+            self.total_blocks,
+            self.file_size,
+        ] {
+            raw.extend_from_slice(&word.to_le_bytes());
+        }
+        raw.extend_from_slice(&self.data);
+        raw.extend_from_slice(&UF2_MAGIC_END.to_le_bytes());
+        raw
+    }
+}
+
+/// a parsed UF2 image, as used by the RP2040 bootloader
+#[derive(Clone, Debug)]
+pub struct Uf2File {
+    pub blocks: Vec<Uf2Block>,
+}
+
+impl Uf2File {
+    pub fn parse(data: &[u8]) -> Result<Uf2File> {
+        if data.is_empty() || data.len() % UF2_BLOCK_SIZE != 0 {
+            err!(Error::InvalidFirmware(format!(
+                "uf2 files must be made of {UF2_BLOCK_SIZE} byte blocks"
+            )))
+        }
+
+        let blocks = data
+            .chunks(UF2_BLOCK_SIZE)
+            .map(Uf2Block::parse)
+            .collect::<Result<Vec<Uf2Block>>>()?;
+
+        // every block should agree on how many blocks there are
+        let total_blocks = blocks[0].total_blocks;
+        if let Some(block) = blocks.iter().find(|block| {
+            block.total_blocks != total_blocks || block.block_number >= block.total_blocks
+        }) {
+            err!(Error::InvalidFirmware(format!(
+                "uf2 block {} of {} is out of sequence",
+                block.block_number, block.total_blocks
+            )))
+        }
+
+        Ok(Uf2File { blocks })
+    }
+
+    pub fn from_path(path: &Path) -> Result<Uf2File> {
+        Uf2File::parse(&read(path)?)
+    }
+
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+}
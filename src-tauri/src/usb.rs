@@ -1,12 +1,14 @@
-use fs_extra::file::TransitProcess;
 use futures::channel::mpsc;
 use futures::channel::mpsc::Receiver;
 use futures::SinkExt;
 use futures::StreamExt;
 use log::debug;
 use log::error;
-use serde::Serialize;
+use log::info;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
 use tauri::{AppHandle, Manager};
 use ts_rs::TS;
 use usb_enumeration::Event as UsbEvent;
@@ -14,25 +16,63 @@ use usb_enumeration::{Event, Observer};
 
 use crate::device::ConnectedDevice;
 use crate::device::ConnectedDeviceType;
-use crate::dfu::install_bridge;
-use crate::dfu::install_rpi;
-use crate::error::Result;
+use crate::dfu::{bridge_image_length, install_bridge};
+use crate::dfu::{install_click, install_uf2, uf2_disk_name};
+use crate::error::{Error, Result};
+use crate::history::{record_attempt, record_bridge_attempt, InstallTelemetry, InstallTimer};
+use crate::profiles::ensure_device_profile;
+use crate::rollback::record_install;
+use crate::state::DfuConfig;
 use crate::state::InstallState;
 use crate::state::InstallerState;
-use crate::USB_POLL_INTERVAL;
+use crate::uf2::UF2_BLOCK_SIZE;
+use crate::watchdog::Watchdog;
+use crate::{DFU_STALL_TIMEOUT, RPI_DISK_TIMEOUT, USB_POLL_INTERVAL};
 
-#[derive(TS, Serialize, Clone, Debug)]
+#[derive(TS, Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[ts(export)]
-pub enum InstallStatus {
+pub enum InstallStage {
     Preparing,
-    Installing,
+    Erasing,
+    Writing,
+    Verifying,
+    Resetting,
 }
 
 #[derive(TS, Serialize, Clone, Debug)]
 #[ts(export)]
 pub struct InstallProgress {
-    status: InstallStatus,
-    progress: u32,
+    stage: InstallStage,
+    bytes_written: u64,
+    total_bytes: u64,
+    percent: f32,
+}
+
+impl InstallProgress {
+    pub fn new(stage: InstallStage, bytes_written: u64, total_bytes: u64) -> Self {
+        let percent = if total_bytes == 0 {
+            0.0
+        } else {
+            (bytes_written as f32 / total_bytes as f32) * 100.0
+        };
+        InstallProgress {
+            stage,
+            bytes_written,
+            total_bytes,
+            percent,
+        }
+    }
+
+    pub fn emit(self, handle: &AppHandle) {
+        debug!("install progress: {:?}", self);
+        handle.emit_all("install_progress", self).unwrap();
+    }
+}
+
+/// send install progress, noting the stage it's in for the install's timings
+fn emit_timed(timer: &Mutex<InstallTimer>, progress: InstallProgress, handle: &AppHandle) {
+    timer.lock().unwrap().enter(progress.stage.clone());
+    progress.emit(handle);
 }
 
 // valid devices have a known device type, and have alphanumeric serial numbers
@@ -47,85 +87,140 @@ fn is_valid_device(device: &ConnectedDevice) -> bool {
             .all(char::is_alphanumeric)
 }
 
-fn install_bridge_devices(handle: AppHandle, binary: &Path) -> Result<()> {
+/// flash a bridge that's in DFU mode - the one with `serial`, if given - returning how long each stage took
+pub fn install_bridge_devices(
+    handle: AppHandle,
+    binary: &Path,
+    serial: Option<&str>,
+    verify: bool,
+) -> Result<InstallTelemetry> {
+    // bail if the install was cancelled while we were waiting for the bootloader
+    let cancelled = handle.state::<InstallState>().cancelled.clone();
+    if cancelled.load(Ordering::SeqCst) {
+        err!(Error::Cancelled)
+    }
+
+    let address = *handle.state::<DfuConfig>().address.read().unwrap();
+
     // these values are for tracking install progress
-    let total_bytes = binary.metadata().unwrap().len() as f32;
-    let mut total_copied_bytes: f32 = 0.0;
+    let total_bytes = bridge_image_length(binary)?;
+    let mut total_copied_bytes: u64 = 0;
+
+    // each stage is timed from when its progress is first sent
+    let timer = Arc::new(Mutex::new(InstallTimer::start()));
 
     // send initial update
-    handle
-        .emit_all(
-            "install_progress",
-            InstallProgress {
-                status: InstallStatus::Preparing,
-                progress: 0,
-            },
-        )
-        .unwrap();
+    emit_timed(
+        &timer,
+        InstallProgress::new(InstallStage::Preparing, 0, total_bytes),
+        &handle,
+    );
+
+    // dfuse erases the first page before it starts writing
+    emit_timed(
+        &timer,
+        InstallProgress::new(InstallStage::Erasing, 0, total_bytes),
+        &handle,
+    );
+
+    // flag the install if the device stops responding mid-transfer
+    let watchdog = Watchdog::start(handle.app_handle(), DFU_STALL_TIMEOUT);
 
     // this is our install progress callback handler - passed to the installer
+    let emitter = handle.app_handle();
+    let progress_watchdog = watchdog.clone();
+    let progress_timer = timer.clone();
     let progress_handler = move |copied_bytes: usize| {
-        // determine percentage
-        total_copied_bytes += copied_bytes as f32;
-        let percentage = ((total_copied_bytes / total_bytes) * 100.0).round() as u32;
-        debug!("total bytes: {total_bytes}, total copied: {total_copied_bytes}, copied: {copied_bytes}, percentage: {percentage}");
+        progress_watchdog.touch();
+        total_copied_bytes += copied_bytes as u64;
 
         // send progress
-        handle
-            .emit_all(
-                "install_progress",
-                InstallProgress {
-                    status: InstallStatus::Installing,
-                    progress: percentage,
-                },
-            )
-            .unwrap();
-
-        // send post install message
-        if percentage >= 100 {
-            handle
-                .state::<InstallState>()
-                .post_install_transition(&handle)
-                .unwrap();
+        emit_timed(
+            &progress_timer,
+            InstallProgress::new(InstallStage::Writing, total_copied_bytes, total_bytes),
+            &emitter,
+        );
+
+        // once everything is written the device gets verified (optionally), detached and reset, then we're done
+        if total_copied_bytes >= total_bytes {
+            // the readback doesn't report progress, so only the transfer is watched
+            progress_watchdog.stop();
+            if verify {
+                emit_timed(
+                    &progress_timer,
+                    InstallProgress::new(InstallStage::Verifying, total_copied_bytes, total_bytes),
+                    &emitter,
+                );
+            } else {
+                emit_timed(
+                    &progress_timer,
+                    InstallProgress::new(InstallStage::Resetting, total_copied_bytes, total_bytes),
+                    &emitter,
+                );
+                emitter
+                    .state::<InstallState>()
+                    .post_install_transition(&emitter)
+                    .unwrap();
+            }
         }
     };
 
     // call the installation method - returns Result<()>
-    install_bridge(binary.to_path_buf(), progress_handler)
+    let result = install_bridge(
+        binary.to_path_buf(),
+        serial.map(str::to_string),
+        verify,
+        address,
+        cancelled,
+        progress_handler,
+    );
+    watchdog.stop();
+    result?;
+
+    // verified installs only finish once the readback has passed
+    if verify {
+        emit_timed(
+            &timer,
+            InstallProgress::new(InstallStage::Resetting, total_bytes, total_bytes),
+            &handle,
+        );
+        handle
+            .state::<InstallState>()
+            .post_install_transition(&handle)?;
+    }
+
+    let telemetry = timer.lock().unwrap().finish(total_bytes);
+    info!(
+        "bridge install took {}ms, writing at {:.0} bytes/s",
+        telemetry.total_duration_ms, telemetry.bytes_per_second
+    );
+    Ok(telemetry)
 }
 
-fn install_rpi_devices(handle: AppHandle, binary: &Path) -> Result<u64> {
+fn install_rpi_devices(
+    handle: AppHandle,
+    binary: &Path,
+    device_type: &ConnectedDeviceType,
+) -> Result<u64> {
+    // bail if the install was cancelled while we were waiting for the bootloader
+    if handle.state::<InstallState>().is_cancelled() {
+        err!(Error::Cancelled)
+    }
+
     // send initial update
-    handle
-        .emit_all(
-            "install_progress",
-            InstallProgress {
-                status: InstallStatus::Preparing,
-                progress: 0,
-            },
-        )
-        .unwrap();
+    let total_bytes = binary.metadata().unwrap().len();
+    InstallProgress::new(InstallStage::Preparing, 0, total_bytes).emit(&handle);
 
     // this is our install progress callback handler - passed to the installer
-    let progress_handler = |process_info: TransitProcess| {
-        // determine percentage
-        let percentage = ((process_info.copied_bytes as f32 / process_info.total_bytes as f32)
-            * 100.0)
-            .round() as u32;
+    let progress_handler = |blocks_written: usize, total_blocks: usize| {
+        // send progress - the disk is written a block at a time, so report it in bytes
+        let bytes_written = (blocks_written * UF2_BLOCK_SIZE) as u64;
+        let total_bytes = (total_blocks * UF2_BLOCK_SIZE) as u64;
+        InstallProgress::new(InstallStage::Writing, bytes_written, total_bytes).emit(&handle);
 
-        // send progress
-        handle
-            .emit_all(
-                "install_progress",
-                InstallProgress {
-                    status: InstallStatus::Installing,
-                    progress: percentage,
-                },
-            )
-            .unwrap();
-
-        // send post install message
-        if percentage >= 100 {
+        // send post install message - the rp2040 resets itself once the uf2 is written
+        if blocks_written >= total_blocks {
+            InstallProgress::new(InstallStage::Resetting, bytes_written, total_bytes).emit(&handle);
             handle
                 .state::<InstallState>()
                 .post_install_transition(&handle)
@@ -134,7 +229,22 @@ fn install_rpi_devices(handle: AppHandle, binary: &Path) -> Result<u64> {
     };
 
     // call the installation method - returns Result<u64>
-    install_rpi(binary.to_path_buf(), progress_handler)
+    let cancelled = handle.state::<InstallState>().cancelled.clone();
+    match device_type {
+        ConnectedDeviceType::Click => install_click(
+            binary.to_path_buf(),
+            RPI_DISK_TIMEOUT,
+            cancelled,
+            progress_handler,
+        ),
+        _ => install_uf2(
+            binary.to_path_buf(),
+            uf2_disk_name(device_type),
+            RPI_DISK_TIMEOUT,
+            cancelled,
+            progress_handler,
+        ),
+    }
 }
 
 fn subscribe() -> Receiver<Event> {
@@ -178,7 +288,8 @@ pub fn setup_usb_listener(handle: AppHandle) {
 
                     // get all device info for all devices
                     for arriving in &mut connected_devices {
-                        match arriving.try_get_all_device_info().await {
+                        ensure_device_profile(&emitter, arriving);
+                        match arriving.try_get_all_device_info(&emitter).await {
                             Ok(_) => state.add_device(arriving.clone(), &emitter).unwrap(),
                             Err(err) => error!("error getting device details: {:?}", err),
                         }
@@ -190,11 +301,15 @@ pub fn setup_usb_listener(handle: AppHandle) {
 
                     if is_valid_device(&arriving) {
                         // get all device info
-                        match arriving.try_get_all_device_info().await {
+                        match arriving.try_get_all_device_info(&emitter).await {
                             Ok(_) => (), // do nothing on success
                             Err(err) => error!("error getting device details: {:?}", err),
                         }
 
+                        // let the frontend react to the device itself, not just the updated list
+                        emitter.emit_all("device_connected", &arriving).unwrap();
+                        ensure_device_profile(&emitter, &arriving);
+
                         // get the mutex to update the state
                         let read_guard = state.current_state.read().unwrap();
 
@@ -214,7 +329,12 @@ pub fn setup_usb_listener(handle: AppHandle) {
                                 }
                             }
                             // if we're in bootloader state, take the device and attempt to update it.
-                            InstallerState::Bootloader { device, binary } => {
+                            InstallerState::Bootloader {
+                                device,
+                                binary,
+                                verify,
+                                release_tag,
+                            } => {
                                 // drop the reader so we don't deadlock in case we need to write
                                 drop(read_guard);
 
@@ -224,9 +344,25 @@ pub fn setup_usb_listener(handle: AppHandle) {
                                     ConnectedDeviceType::Bridge4
                                     | ConnectedDeviceType::Bridge6
                                     | ConnectedDeviceType::BridgeBootloader => {
-                                        match install_bridge_devices(emitter.app_handle(), &binary)
-                                        {
-                                            Ok(_) => (), // do nothing
+                                        let result = install_bridge_devices(
+                                            emitter.app_handle(),
+                                            &binary,
+                                            arriving.serial_number.as_deref(),
+                                            verify,
+                                        );
+                                        record_bridge_attempt(
+                                            &emitter,
+                                            &device,
+                                            release_tag.clone(),
+                                            &result,
+                                        );
+                                        match result {
+                                            Ok(_) => record_install(
+                                                &emitter,
+                                                &device,
+                                                &binary,
+                                                release_tag,
+                                            ),
                                             Err(err) => {
                                                 error!("unable to continue install: {:?}", err)
                                             }
@@ -235,8 +371,24 @@ pub fn setup_usb_listener(handle: AppHandle) {
                                     ConnectedDeviceType::Click
                                     | ConnectedDeviceType::ULoop
                                     | ConnectedDeviceType::RPBootloader => {
-                                        match install_rpi_devices(emitter.app_handle(), &binary) {
-                                            Ok(_) => (), // do nothing
+                                        let result = install_rpi_devices(
+                                            emitter.app_handle(),
+                                            &binary,
+                                            &device.device_type,
+                                        );
+                                        record_attempt(
+                                            &emitter,
+                                            &device,
+                                            release_tag.clone(),
+                                            &result,
+                                        );
+                                        match result {
+                                            Ok(_) => record_install(
+                                                &emitter,
+                                                &device,
+                                                &binary,
+                                                release_tag,
+                                            ),
                                             Err(err) => {
                                                 error!("unable to continue install: {:?}", err)
                                             }
@@ -251,6 +403,9 @@ pub fn setup_usb_listener(handle: AppHandle) {
                 UsbEvent::Disconnect(device) => {
                     let leaving = ConnectedDevice::from(&device);
                     if leaving.device_type != ConnectedDeviceType::Unknown {
+                        emitter
+                            .emit_all("device_disconnected", &leaving.serial_number)
+                            .unwrap();
                         state.remove_device(leaving, &emitter).unwrap();
                     }
                 }
@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use tauri::{api::path::app_data_dir, Config};
+
+use crate::{
+    error::{Error, Result},
+    github::Release,
+};
+
+const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+/// a file (or directory) in the app data dir, e.g. `settings.json`
+pub fn app_data_file(config: &Config, name: &str) -> Result<PathBuf> {
+    match app_data_dir(config) {
+        Some(dir) => Ok(dir.join(name)),
+        None => err!(Error::IO(
+            "unable to determine app data directory".to_string()
+        )),
+    }
+}
+
+/// format a byte count for people, e.g. "924 KB" or "1.2 MB"
+pub fn format_bytes(n: u64) -> String {
+    if n < 1024 {
+        return format!("{n} B");
+    }
+
+    let mut value = n as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    // kilobytes don't need the precision
+    match unit {
+        0 => format!("{:.0} {}", value, UNITS[unit]),
+        _ => format!("{:.1} {}", value, UNITS[unit]),
+    }
+}
+
+/// how much we'd download to fetch every asset in these releases
+pub fn total_download_size(releases: &[Release]) -> u64 {
+    releases
+        .iter()
+        .flat_map(|release| release.assets.iter())
+        .map(|asset| asset.size)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::format_bytes;
+
+    #[test]
+    fn formats_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1024), "1 KB");
+        assert_eq!(format_bytes(1_000_000), "977 KB");
+        assert_eq!(format_bytes(1_258_291), "1.2 MB");
+    }
+}
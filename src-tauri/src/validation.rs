@@ -1,10 +1,25 @@
 // COMPATIBILITY
 
-use std::path::PathBuf;
+use std::{
+    fs::{read, File},
+    io::copy,
+    path::PathBuf,
+};
 
 use log::debug;
+use semver::Version;
+use serde::Serialize;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use ts_rs::TS;
 
-use crate::device::{ConnectedDevice, ConnectedDeviceType};
+use crate::{
+    device::{ConnectedDevice, ConnectedDeviceType},
+    dfu_suffix::{parse_dfu_suffix_bytes, DFUSE_PREFIX_SIGNATURE},
+    error::{Error, Result},
+    uf2::{Uf2File, UF2_MAGIC_START0, UF2_MAGIC_START1},
+    USB_BRIDGE_PRODUCT_DFU_ID, USB_BRIDGE_VENDOR_ID,
+};
 
 fn after<'value>(value: &'value str, a: &str) -> &'value str {
     // Find the string and return the part after.
@@ -57,6 +72,8 @@ fn _is_not_diag(device: &ConnectedDevice, file_name: &str) -> bool {
 }
 
 pub fn is_name_compatible(device: &ConnectedDevice, file_name: &str, allow_diag: bool) -> bool {
+    // release assets aren't always named consistently, so don't let casing get in the way
+    let file_name = file_name.to_lowercase();
     match &device.device_type {
         // assume format: bridgeX_v1.2.1.1.bin || device_v1.0.0.0.uf2
         // the last number in the version is the compatible revision
@@ -80,11 +97,207 @@ pub fn is_file_compatible(device: &ConnectedDevice, binary: &PathBuf, allow_diag
     is_name_compatible(device, &file_name, allow_diag)
 }
 
+/// parse a firmware version or release tag (e.g. `v1.2.1.1`) into semver, ignoring anything past the patch number
+pub fn parse_version(value: &str) -> Option<Version> {
+    let trimmed = value.trim().trim_start_matches(|c| c == 'v' || c == 'V');
+    let parts: Vec<&str> = trimmed.split('.').take(3).collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    Version::parse(&parts.join(".")).ok()
+}
+
+// FIRMWARE FILES
+
+#[derive(TS, Serialize, Clone, Debug, PartialEq)]
+#[ts(export)]
+pub enum FirmwareFormat {
+    Dfu,
+    Uf2,
+    Bin,
+    Unknown,
+}
+
+#[derive(TS, Serialize, Clone, Debug)]
+#[ts(export)]
+pub struct FirmwareFileInfo {
+    pub file_size: u64,
+    pub format: FirmwareFormat,
+    pub appears_valid: bool,
+    pub reason: Option<String>,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn detect_format(binary: &PathBuf, data: &[u8]) -> FirmwareFormat {
+    // trust the contents over the extension when they're recognisable
+    if data.starts_with(DFUSE_PREFIX_SIGNATURE) {
+        return FirmwareFormat::Dfu;
+    }
+    if data.len() >= 8
+        && read_u32(data, 0) == UF2_MAGIC_START0
+        && read_u32(data, 4) == UF2_MAGIC_START1
+    {
+        return FirmwareFormat::Uf2;
+    }
+
+    match binary
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .as_deref()
+    {
+        Some("dfu") => FirmwareFormat::Dfu,
+        Some("uf2") => FirmwareFormat::Uf2,
+        Some("bin") => FirmwareFormat::Bin,
+        _ => FirmwareFormat::Unknown,
+    }
+}
+
+fn check_dfu(data: &[u8]) -> std::result::Result<(), String> {
+    let suffix = parse_dfu_suffix_bytes(data).map_err(|err| err.to_string())?;
+
+    // 0xFFFF is a wildcard, otherwise it has to target the stm32 bootloader
+    if (suffix.vendor_id != 0xFFFF && suffix.vendor_id != USB_BRIDGE_VENDOR_ID)
+        || (suffix.product_id != 0xFFFF && suffix.product_id != USB_BRIDGE_PRODUCT_DFU_ID)
+    {
+        return Err(format!(
+            "dfu file targets a different device: {:04x}:{:04x}",
+            suffix.vendor_id, suffix.product_id
+        ));
+    }
+
+    Ok(())
+}
+
+fn check_uf2(data: &[u8]) -> std::result::Result<(), String> {
+    match Uf2File::parse(data) {
+        Ok(image) => {
+            debug!("uf2 image contains {} blocks", image.block_count());
+            Ok(())
+        }
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn check_bin(data: &[u8]) -> std::result::Result<(), String> {
+    if data.len() < 8 {
+        return Err("file is too small to be a firmware image".to_string());
+    }
+
+    // the vector table starts with the initial stack pointer, which lives in sram
+    let stack_pointer = read_u32(data, 0);
+    if stack_pointer & 0xFFF0_0000 != 0x2000_0000 {
+        return Err(format!(
+            "binary does not start with a valid vector table: {stack_pointer:#010x}"
+        ));
+    }
+    Ok(())
+}
+
+/// inspect a local firmware file, explaining why it can't be installed on a device
+pub fn inspect_firmware_file(
+    device: &ConnectedDevice,
+    binary: &PathBuf,
+) -> Result<FirmwareFileInfo> {
+    let data = read(binary)?;
+    let format = detect_format(binary, &data);
+
+    let result = match (&device.device_type, &format) {
+        (
+            ConnectedDeviceType::Bridge6
+            | ConnectedDeviceType::Bridge4
+            | ConnectedDeviceType::BridgeBootloader,
+            FirmwareFormat::Dfu,
+        ) => check_dfu(&data),
+        (
+            ConnectedDeviceType::Bridge6
+            | ConnectedDeviceType::Bridge4
+            | ConnectedDeviceType::BridgeBootloader,
+            FirmwareFormat::Bin,
+        ) => check_bin(&data),
+        (
+            ConnectedDeviceType::Click
+            | ConnectedDeviceType::ULoop
+            | ConnectedDeviceType::RPBootloader,
+            FirmwareFormat::Uf2,
+        ) => check_uf2(&data),
+        (ConnectedDeviceType::Unknown, _) => Err("device type is unknown".to_string()),
+        (device_type, format) => Err(format!(
+            "{format:?} files can't be installed on a {device_type:?}"
+        )),
+    }
+    .and_then(|_| match is_file_compatible(device, binary, true) {
+        true => Ok(()),
+        false => Err("file name does not match this device or hardware revision".to_string()),
+    });
+    debug!("inspected {}: {:?}", binary.display(), result);
+
+    Ok(FirmwareFileInfo {
+        file_size: data.len() as u64,
+        format,
+        appears_valid: result.is_ok(),
+        reason: result.err(),
+    })
+}
+
+// INTEGRITY
+
+fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// compute the lowercase hex encoded SHA-256 digest of a file on disk
+pub fn file_sha256(path: &PathBuf) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    copy(&mut file, &mut hasher)?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// compute the lowercase hex encoded SHA-1 digest of a file on disk
+pub fn file_sha1(path: &PathBuf) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha1::new();
+    copy(&mut file, &mut hasher)?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// verify a file against an expected checksum - accepts either `sha256:<hex>` or a bare hex digest
+pub fn verify_checksum(path: &PathBuf, expected: &str) -> Result<()> {
+    let expected = expected
+        .strip_prefix("sha256:")
+        .unwrap_or(expected)
+        .to_lowercase();
+    let got = file_sha256(path)?;
+    debug!(
+        "checksum for {} - expected: {expected}, got: {got}",
+        path.display()
+    );
+
+    if got == expected {
+        Ok(())
+    } else {
+        Err(Error::ChecksumMismatch { expected, got })
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use crate::{
         device::{ConnectedDevice, DeviceDetails},
-        validation::is_name_compatible,
+        dfu_suffix::{compute_dfu_crc, parse_dfu_suffix_bytes},
+        error::Error,
+        validation::{is_name_compatible, parse_version},
     };
 
     #[test]
@@ -168,4 +381,95 @@ mod tests {
 
         // assert_eq!(result, 4);
     }
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(
+            parse_version("v1.2.1.1"),
+            semver::Version::parse("1.2.1").ok()
+        );
+        assert_eq!(parse_version("1.0.4"), semver::Version::parse("1.0.4").ok());
+        assert_eq!(parse_version("v1.2"), None);
+        assert_eq!(parse_version("latest"), None);
+    }
+
+    #[test]
+    fn computes_dfu_crc() {
+        // the standard crc32 check values, without the final xor
+        assert_eq!(compute_dfu_crc(b"123456789"), 0x340BC6D9);
+        assert_eq!(compute_dfu_crc(b""), 0xFFFFFFFF);
+        assert_eq!(compute_dfu_crc(b"a"), !0xE8B7BE43);
+        assert_eq!(
+            compute_dfu_crc(b"The quick brown fox jumps over the lazy dog"),
+            !0x414FA339
+        );
+    }
+
+    // a single target, single element DfuSe image as ST's DfuSe tools write it
+    fn dfuse_image(address: u32, payload: &[u8]) -> Vec<u8> {
+        let mut element = address.to_le_bytes().to_vec();
+        element.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        element.extend_from_slice(payload);
+
+        let mut target = b"Target".to_vec();
+        target.push(0); // alternate setting
+        target.extend_from_slice(&1u32.to_le_bytes()); // named
+        let mut name = [0u8; 255];
+        name[..14].copy_from_slice(b"Internal Flash");
+        target.extend_from_slice(&name);
+        target.extend_from_slice(&(element.len() as u32).to_le_bytes());
+        target.extend_from_slice(&1u32.to_le_bytes()); // elements
+        target.extend_from_slice(&element);
+
+        let mut image = b"DfuSe".to_vec();
+        image.push(0x01);
+        image.extend_from_slice(&(11 + target.len() as u32).to_le_bytes());
+        image.push(1); // targets
+        image.extend_from_slice(&target);
+
+        image.extend_from_slice(&[0xFF, 0xFF, 0x11, 0xDF, 0x83, 0x04, 0x1A, 0x01]);
+        image.extend_from_slice(b"UFD");
+        image.push(16);
+        let crc = compute_dfu_crc(&image);
+        image.extend_from_slice(&crc.to_le_bytes());
+        image
+    }
+
+    #[test]
+    fn rejects_prefixed_dfuse_images() {
+        let image = dfuse_image(
+            0x0800_0000,
+            &[0x00, 0x50, 0x00, 0x20, 0x01, 0x02, 0x00, 0x08],
+        );
+        assert!(matches!(
+            parse_dfu_suffix_bytes(&image),
+            Err(Error::InvalidFirmware(_))
+        ));
+    }
+
+    // append a dfuse suffix for 0483:df11 with a correct crc
+    fn with_dfu_suffix(data: &[u8]) -> Vec<u8> {
+        let mut image = data.to_vec();
+        image.extend_from_slice(&[0xFF, 0xFF, 0x11, 0xDF, 0x83, 0x04, 0x1A, 0x01]);
+        image.extend_from_slice(b"UFD");
+        image.push(16);
+        let crc = compute_dfu_crc(&image);
+        image.extend_from_slice(&crc.to_le_bytes());
+        image
+    }
+
+    proptest! {
+        #[test]
+        fn dfu_crc_round_trips(data in proptest::collection::vec(any::<u8>(), 0..4096)) {
+            let mut image = with_dfu_suffix(&data);
+            prop_assert!(parse_dfu_suffix_bytes(&image).is_ok());
+
+            // any corruption of the image should be caught
+            let index = data.len() / 2;
+            if index < data.len() {
+                image[index] ^= 0xFF;
+                prop_assert!(parse_dfu_suffix_bytes(&image).is_err());
+            }
+        }
+    }
 }
@@ -0,0 +1,86 @@
+use log::warn;
+use semver::VersionReq;
+
+use crate::{
+    error::{Error, Result},
+    github::Release,
+    validation::parse_version,
+};
+
+/// parse a version constraint like `^1.0` or `>=1.2.0, <2.0.0`
+pub fn parse_range(range: &str) -> Result<VersionReq> {
+    VersionReq::parse(range.trim())
+        .map_err(|err| Error::Other(format!("invalid version range \"{range}\": {err}")))
+}
+
+/// the releases whose tag falls within a version constraint - tags that aren't versions are left out
+pub fn filter_by_semver_range(releases: &[Release], range: &str) -> Result<Vec<Release>> {
+    let range = parse_range(range)?;
+    Ok(releases
+        .iter()
+        .filter(|release| {
+            parse_version(&release.tag_name).map_or(false, |version| range.matches(&version))
+        })
+        .cloned()
+        .collect())
+}
+
+/// refuse to install an older version over a newer one, unless `allow_downgrade` is set.
+/// versions that can't be parsed aren't compared - there's nothing to go on.
+pub fn check_downgrade(current: &str, target: &str, allow_downgrade: bool) -> Result<()> {
+    match (parse_version(current), parse_version(target)) {
+        (Some(current_version), Some(target_version)) if target_version < current_version => {
+            if !allow_downgrade {
+                err!(Error::Other(format!(
+                    "downgrade not allowed: current version {current} is newer than target {target}"
+                )))
+            }
+            warn!("downgrading from {current} to {target}");
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        fixtures,
+        github::Release,
+        version::{check_downgrade, filter_by_semver_range},
+    };
+
+    fn mock_release(tag: &str) -> Release {
+        fixtures::mock_release(tag, "2023-01-01T00:00:00Z")
+    }
+
+    #[test]
+    fn filters_releases_by_range() {
+        let releases: Vec<Release> = ["v0.9.0.1", "v1.0.0.1", "v1.2.3.1", "v2.0.0.1", "nightly"]
+            .into_iter()
+            .map(mock_release)
+            .collect();
+        let tags = |range: &str| -> Vec<String> {
+            filter_by_semver_range(&releases, range)
+                .unwrap()
+                .into_iter()
+                .map(|release| release.tag_name)
+                .collect()
+        };
+
+        assert_eq!(tags("^1.0"), vec!["v1.0.0.1", "v1.2.3.1"]);
+        assert_eq!(tags(">=1.2.0, <2.0.0"), vec!["v1.2.3.1"]);
+        assert_eq!(tags(">=2"), vec!["v2.0.0.1"]);
+        assert!(filter_by_semver_range(&releases, "one point oh").is_err());
+    }
+
+    #[test]
+    fn blocks_downgrades() {
+        assert!(check_downgrade("1.2.0", "v1.3.0.1", false).is_ok());
+        assert!(check_downgrade("1.2.0", "v1.2.0.2", false).is_ok());
+        assert!(check_downgrade("1.2.0", "v1.1.9.1", false).is_err());
+        assert!(check_downgrade("1.2.0", "v1.1.9.1", true).is_ok());
+        // nothing to compare against
+        assert!(check_downgrade("unknown", "v1.1.9.1", false).is_ok());
+    }
+}
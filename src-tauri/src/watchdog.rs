@@ -0,0 +1,75 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use log::{debug, warn};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use ts_rs::TS;
+
+use crate::WATCHDOG_CHECK_INTERVAL;
+
+/// sent with `install_stalled` when a flash hasn't made progress in a while
+#[derive(TS, Serialize, Clone, Debug)]
+#[ts(export)]
+pub struct InstallStalled {
+    seconds_since_progress: u64,
+}
+
+/// watches an install for progress - dfu transfers can stop responding without ever returning an error,
+/// so if nothing touches the watchdog for `stall_timeout` an `install_stalled` event is emitted.
+#[derive(Clone)]
+pub struct Watchdog {
+    last_touch: Arc<Mutex<Instant>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Watchdog {
+    /// start watching - runs until `stop` is called
+    pub fn start(handle: AppHandle, stall_timeout: Duration) -> Watchdog {
+        let watchdog = Watchdog {
+            last_touch: Arc::new(Mutex::new(Instant::now())),
+            stopped: Arc::new(AtomicBool::new(false)),
+        };
+
+        let watcher = watchdog.clone();
+        tauri::async_runtime::spawn(async move {
+            // only report a stall once, until progress picks up again
+            let mut reported = false;
+            loop {
+                tokio::time::sleep(WATCHDOG_CHECK_INTERVAL).await;
+                if watcher.stopped.load(Ordering::SeqCst) {
+                    debug!("install watchdog stopped");
+                    break;
+                }
+
+                let idle = watcher.last_touch.lock().unwrap().elapsed();
+                if idle < stall_timeout {
+                    reported = false;
+                } else if !reported {
+                    warn!("install has made no progress in {:?}", idle);
+                    let payload = InstallStalled {
+                        seconds_since_progress: idle.as_secs(),
+                    };
+                    handle.emit_all("install_stalled", payload).unwrap();
+                    reported = true;
+                }
+            }
+        });
+        watchdog
+    }
+
+    /// record that the install is still making progress
+    pub fn touch(&self) {
+        *self.last_touch.lock().unwrap() = Instant::now();
+    }
+
+    /// stop watching - the install finished, one way or another
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+}
@@ -24,7 +24,7 @@ function AvailableDevices({ devices }: { devices: ConnectedDevice[] }) {
 
   const onLocalInstall = async (device: ConnectedDevice) => {
     await invoke("local_binary", { device }).catch((e) => {
-      if (!e.IO) {
+      if (e.kind !== "IO") {
         console.log(e);
         setErrorDevice(device);
         setIsOpen(true);
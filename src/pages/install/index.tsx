@@ -29,8 +29,14 @@ function Install() {
         switch (status) {
             case "Preparing":
                 return "Preparing device..."
-            case "Installing":
+            case "Erasing":
+                return "Erasing..."
+            case "Writing":
                 return "Installing..."
+            case "Verifying":
+                return "Verifying..."
+            case "Resetting":
+                return "Restarting device..."
             case "Waiting":
                 return "Waiting for device..."
         }
@@ -40,8 +46,8 @@ function Install() {
     useEffect(() => {
         const installListener = listen<InstallProgress>('install_progress', event => {
             console.log(event.payload)
-            setStatus(event.payload.status)
-            setPercent(event.payload.progress)
+            setStatus(event.payload.stage)
+            setPercent(Math.round(event.payload.percent))
         })
 
         // destructor